@@ -0,0 +1,73 @@
+// Measures the cost of one simulation tick for growing vehicle counts.
+// Run with `cargo bench --bench tick`.
+
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use smart_road::geometry::Geometry;
+use smart_road::physics_engine::PhysicsEngine;
+use smart_road::simulation::DEFAULT_TIMESTEP;
+use smart_road::spawner::{Spawner, TURNS};
+use smart_road::vehicle::{Lane, Vehicle};
+use smart_road::{MovementDirection, Position, Simulation, TurnDirection};
+use std::time::{Duration, Instant};
+
+const TICKS: u32 = 20;
+
+// Scatter small vehicles over the whole world so every grid cell sees traffic
+fn populate(simulation: &mut Simulation, count: usize, rng: &mut StdRng) {
+    let geometry = Geometry::default();
+    let directions = [MovementDirection::Up, MovementDirection::Down, MovementDirection::Left, MovementDirection::Right];
+    for _ in 0..count {
+        let position = Position::new(rng.gen_range(0.0..geometry.world_width), rng.gen_range(0.0..geometry.world_height));
+        let direction = directions[rng.gen_range(0..directions.len())];
+        let mut vehicle = Vehicle::new(direction, TurnDirection::Straight, 30.0, position, Lane::Middle);
+        vehicle.size = 4.0;
+        simulation.add_vehicle(vehicle);
+    }
+}
+
+fn main() {
+    for &count in &[1_000, 5_000, 10_000] {
+        let mut rng = StdRng::seed_from_u64(42);
+        let mut simulation = Simulation::new(PhysicsEngine::new(5.0, 3000.0), DEFAULT_TIMESTEP, 42);
+        populate(&mut simulation, count, &mut rng);
+
+        let start = Instant::now();
+        for _ in 0..TICKS {
+            simulation.step();
+        }
+        let per_tick = start.elapsed() / TICKS;
+        println!("{:>6} vehicles: {:>10.3?} per tick", count, per_tick);
+    }
+
+    // Reference point: what the old full scan costs for leader detection alone
+    let mut rng = StdRng::seed_from_u64(42);
+    let mut simulation = Simulation::new(PhysicsEngine::new(5.0, 3000.0), DEFAULT_TIMESTEP, 42);
+    populate(&mut simulation, 10_000, &mut rng);
+    let vehicles = simulation.vehicles();
+    let intersection_manager = simulation.intersection_manager();
+    let start = Instant::now();
+    for vehicle in vehicles {
+        std::hint::black_box(intersection_manager.get_vehicle_ahead_in_same_direction(vehicle, vehicles));
+    }
+    let elapsed: Duration = start.elapsed();
+    println!(" 10000 vehicles: {:>10.3?} for a full-scan leader pass", elapsed);
+
+    // Entry occupancy is checked for every entry lane on every tick that spawns
+    // or has a backlog; the grid lookup against a scan of every vehicle
+    simulation.step();
+    let entries: Vec<(usize, TurnDirection)> =
+        simulation.network().entry_links().flat_map(|link| TURNS.map(|turn| (link, turn))).collect();
+    let start = Instant::now();
+    for &(link, turn_direction) in &entries {
+        std::hint::black_box(simulation.entry_clear(link, turn_direction));
+    }
+    println!(" 10000 vehicles: {:>10.3?} for an entry check of every lane", start.elapsed());
+    let start = Instant::now();
+    for &(link, turn_direction) in &entries {
+        let direction = simulation.network().link(link).direction;
+        let (entry, _) = simulation.network().link_geometry(link).spawn_point(direction, turn_direction);
+        std::hint::black_box(Spawner::entry_clear(simulation.vehicles(), entry, 55.0, 5.0));
+    }
+    println!(" 10000 vehicles: {:>10.3?} for a full-scan entry check of every lane", start.elapsed());
+}
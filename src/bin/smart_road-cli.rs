@@ -0,0 +1,152 @@
+// Runs a scenario without a window, for CI and parameter studies.
+//
+//   smart_road-cli --scenario <path> [--duration <s>] [--realtime <factor>]
+//                  [--stats <path>] [--contacts <path>] [--verbose]
+//                  [--trace <path> ...] [--record <path>]
+//   smart_road-cli --replay <recording> [--stats <path>] ...
+//   smart_road-cli --scenario <path> --print-conflicts
+//
+// plus the flags shared with the SDL frontend, see `smart_road::cli`. The run
+// lasts for the scenario duration, or `--duration` seconds of simulated time,
+// and goes as fast as possible unless `--realtime` asks for a fixed ratio of
+// simulated to real time. Statistics are written as JSON to `--stats`, or to
+// stdout, and every collision and close call as CSV to `--contacts`. A replay
+// re-runs a recording made by either frontend for as many ticks as it lasted.
+// `--print-conflicts` prints the conflict matrix the scenario's intersections
+// use, overrides included, and exits.
+
+use smart_road::cli;
+use smart_road::Simulation;
+use smart_road::collision::{Contact, ContactKind};
+use smart_road::recording::{Recorder, Recording};
+use std::io::Write;
+use std::time::{Duration, Instant};
+
+fn main() {
+    if let Err(e) = run() {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    }
+}
+
+fn run() -> Result<(), String> {
+    let args = cli::args();
+    let realtime = cli::parse_arg::<f32>(&args, "--realtime")?;
+    if let Some(factor) = realtime {
+        if factor.is_nan() || factor <= 0.0 {
+            return Err(format!("--realtime must be positive, got {}", factor));
+        }
+    }
+
+    if let Some(path) = cli::arg_value(&args, "--replay")? {
+        let recording = Recording::load(&path).map_err(|e| format!("Could not load recording: {}", e))?;
+        eprintln!("Seed: {}", recording.seed);
+        let (mut simulation, mut replayer) = recording.start()?;
+        simulation.set_log_events(cli::has_flag(&args, "--verbose"));
+        simulation.set_tracer(cli::tracer_from_args(&args)?);
+        let start = Instant::now();
+        while !replayer.is_finished(&simulation) {
+            replayer.step(&mut simulation);
+            pace(&simulation, start, realtime);
+        }
+        return finish(&args, simulation, start);
+    }
+
+    let mut scenario = cli::scenario_from_args(&args)?;
+    if cli::has_flag(&args, "--print-conflicts") {
+        let network = scenario.network()?;
+        print!("{}", network.manager(0).conflict_matrix().to_table());
+        return Ok(());
+    }
+    if let Some(seconds) = cli::parse_arg::<f32>(&args, "--duration")? {
+        scenario.duration = Some(
+            Duration::try_from_secs_f32(seconds)
+                .ok()
+                .filter(|duration| !duration.is_zero())
+                .ok_or_else(|| format!("--duration must be positive, got {}", seconds))?,
+        );
+    }
+    let duration = scenario
+        .duration
+        .ok_or("the scenario has no duration, set one in the file or with --duration")?;
+
+    let seed = scenario.seed.unwrap_or_else(rand::random);
+    eprintln!("Seed: {}", seed);
+    let mut simulation = scenario.build(seed).map_err(|e| format!("Invalid scenario: {}", e))?;
+    simulation.set_log_events(cli::has_flag(&args, "--verbose"));
+    simulation.set_tracer(cli::tracer_from_args(&args)?);
+
+    let start = Instant::now();
+    while simulation.time() < duration && !simulation.is_halted() {
+        simulation.step();
+        pace(&simulation, start, realtime);
+    }
+    if let Some(path) = cli::arg_value(&args, "--record")? {
+        Recorder::new(&scenario, seed).finish(&simulation).save(&path)?;
+    }
+    finish(&args, simulation, start)
+}
+
+// Hold the simulation back to `realtime` times real time, if given
+fn pace(simulation: &Simulation, start: Instant, realtime: Option<f32>) {
+    if let Some(factor) = realtime {
+        let target = simulation.time().div_f32(factor);
+        let elapsed = start.elapsed();
+        if target > elapsed {
+            std::thread::sleep(target - elapsed);
+        }
+    }
+}
+
+// Report the run and write its statistics, trace and contacts
+fn finish(args: &[String], mut simulation: Simulation, start: Instant) -> Result<(), String> {
+    eprintln!(
+        "Simulated {:.1} s in {:.2} s{}",
+        simulation.time().as_secs_f32(),
+        start.elapsed().as_secs_f32(),
+        if simulation.is_halted() { ", halted on collision" } else { "" }
+    );
+    let reservations = simulation.reservation_counts();
+    eprintln!(
+        "Reservations: {} active, {} expired, {} cancelled, {} revoked",
+        reservations.active, reservations.expired, reservations.cancelled, reservations.revoked
+    );
+
+    let statistics = simulation.statistics().to_json();
+    match cli::arg_value(args, "--stats")? {
+        Some(path) => std::fs::write(&path, statistics + "\n").map_err(|e| format!("{}: {}", path, e))?,
+        None => println!("{}", statistics),
+    }
+    if let Some(path) = cli::arg_value(args, "--trace")? {
+        simulation.finish_trace().map_err(|e| format!("{}: {}", path, e))?;
+    }
+    if let Some(path) = cli::arg_value(args, "--contacts")? {
+        write_contacts(&path, simulation.contacts()).map_err(|e| format!("{}: {}", path, e))?;
+    }
+    Ok(())
+}
+
+fn write_contacts(path: &str, contacts: &[Contact]) -> std::io::Result<()> {
+    let mut file = std::io::BufWriter::new(std::fs::File::create(path)?);
+    writeln!(file, "time,kind,first_id,second_id,first_x,first_y,second_x,second_y")?;
+    for contact in contacts {
+        let kind = match contact.kind {
+            ContactKind::Collision => "collision",
+            ContactKind::CloseCall => "close_call",
+        };
+        let (first, second) = contact.positions;
+        writeln!(
+            file,
+            "{:.4},{},{},{},{:.2},{:.2},{:.2},{:.2}",
+            contact.time.as_secs_f64(),
+            kind,
+            contact.vehicle_ids.0,
+            contact.vehicle_ids.1,
+            first.x,
+            first.y,
+            second.x,
+            second.y
+        )?;
+    }
+    file.flush()
+}
@@ -0,0 +1,105 @@
+use crate::demand::DemandProfile;
+use crate::policy::PolicyKind;
+use crate::scenario::Scenario;
+use crate::trace::{TraceField, TraceFormat, TraceWriter, Tracer};
+use std::str::FromStr;
+use std::time::Duration;
+
+// Command-line handling shared by the SDL frontend and the headless runner.
+// Flags understood by both:
+//   --scenario <path>      scenario file, see `Scenario`
+//   --seed <n>             overrides the scenario seed
+//   --grid <rows>x<cols>   grid of intersections, keeping the scenario's grid.spacing
+//                          or `DEFAULT_GRID_SPACING` when it sets none
+//   --demand <path>        demand CSV, see `DemandProfile::from_csv`
+//   --halt-on-collision    stop the run at the first collision
+//   --policy <name>        intersection control policy, see `PolicyKind`
+//   --trace <path>         write vehicle states, CSV or JSON Lines by extension
+//   --trace-format <f>     csv or jsonl, overriding the extension
+//   --trace-interval <s>   simulated seconds between samples, every tick by default
+//   --trace-fields <list>  comma separated `TraceField` names, all by default
+
+// Arguments after the program name
+pub fn args() -> Vec<String> {
+    std::env::args().skip(1).collect()
+}
+
+// Value following `flag`, if the flag is present
+pub fn arg_value(args: &[String], flag: &str) -> Result<Option<String>, String> {
+    match args.iter().position(|arg| arg == flag) {
+        Some(index) => match args.get(index + 1) {
+            Some(value) => Ok(Some(value.clone())),
+            None => Err(format!("{} requires a value", flag)),
+        },
+        None => Ok(None),
+    }
+}
+
+// Value following `flag` parsed as `T`
+pub fn parse_arg<T: FromStr>(args: &[String], flag: &str) -> Result<Option<T>, String> {
+    match arg_value(args, flag)? {
+        Some(value) => value.parse().map(Some).map_err(|_| format!("invalid value '{}' for {}", value, flag)),
+        None => Ok(None),
+    }
+}
+
+pub fn has_flag(args: &[String], flag: &str) -> bool {
+    args.iter().any(|arg| arg == flag)
+}
+
+// Scenario from `--scenario`, or the defaults, with the other shared flags applied on top
+pub fn scenario_from_args(args: &[String]) -> Result<Scenario, String> {
+    let mut scenario = match arg_value(args, "--scenario")? {
+        Some(path) => Scenario::load(&path).map_err(|e| format!("Could not load scenario: {}", e))?,
+        None => Scenario::default(),
+    };
+    if let Some(seed) = parse_arg(args, "--seed")? {
+        scenario.seed = Some(seed);
+    }
+    if let Some(value) = arg_value(args, "--grid")? {
+        let (rows, cols) = parse_grid(&value).map_err(|e| format!("Invalid --grid: {}", e))?;
+        scenario.grid_rows = rows;
+        scenario.grid_cols = cols;
+    }
+    if let Some(name) = arg_value(args, "--policy")? {
+        scenario.policy = PolicyKind::from_name(&name)?;
+    }
+    if has_flag(args, "--halt-on-collision") {
+        scenario.halt_on_collision = true;
+    }
+    if let Some(path) = arg_value(args, "--demand")? {
+        scenario.demand = Some(DemandProfile::load_csv(&path).map_err(|e| format!("Could not load demand: {}", e))?);
+    }
+    Ok(scenario)
+}
+
+// Tracer described by the `--trace` flags, None without `--trace`
+pub fn tracer_from_args(args: &[String]) -> Result<Option<Tracer>, String> {
+    let path = match arg_value(args, "--trace")? {
+        Some(path) => path,
+        None => return Ok(None),
+    };
+    let format = match arg_value(args, "--trace-format")? {
+        Some(name) => TraceFormat::parse(&name).ok_or_else(|| format!("unknown trace format '{}', expected csv or jsonl", name))?,
+        None => TraceFormat::for_path(&path),
+    };
+    let interval = match parse_arg::<f32>(args, "--trace-interval")? {
+        Some(seconds) => Duration::try_from_secs_f32(seconds)
+            .map_err(|_| format!("--trace-interval must not be negative, got {}", seconds))?,
+        None => Duration::ZERO,
+    };
+    let fields = match arg_value(args, "--trace-fields")? {
+        Some(list) => TraceField::parse_list(&list)?,
+        None => TraceField::ALL.to_vec(),
+    };
+    let writer = TraceWriter::create(&path, format, fields).map_err(|e| format!("{}: {}", path, e))?;
+    Ok(Some(Tracer::new(Box::new(writer), interval)))
+}
+
+// Parse `<rows>x<cols>`
+fn parse_grid(value: &str) -> Result<(usize, usize), String> {
+    let (rows, cols) = value.split_once('x').ok_or_else(|| format!("expected <rows>x<cols>, got '{}'", value))?;
+    let rows = rows.parse().map_err(|_| format!("invalid row count '{}'", rows))?;
+    let cols = cols.parse().map_err(|_| format!("invalid column count '{}'", cols))?;
+    Ok((rows, cols))
+}
@@ -0,0 +1,214 @@
+use crate::spatial_index::SpatialGrid;
+use crate::vehicle::Vehicle;
+use crate::Position;
+use std::collections::HashMap;
+use std::time::Duration;
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum ContactKind {
+    // The two vehicle footprints overlap
+    Collision,
+    // The gap between the footprints is below the close-call threshold
+    CloseCall,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Contact {
+    pub kind: ContactKind,
+    pub vehicle_ids: (i32, i32),
+    pub positions: (Position, Position),
+    pub time: Duration,
+}
+
+#[derive(Debug, Clone)]
+pub struct CollisionDetector {
+    // Fraction of the physics safety distance under which two vehicles count as a close call
+    close_call_fraction: f32,
+    halt_on_collision: bool,
+    // Pairs currently in contact, so a lasting contact is only reported when it starts
+    // or escalates from a close call to a collision
+    active: HashMap<(i32, i32), ContactKind>,
+}
+
+impl CollisionDetector {
+    pub fn new(close_call_fraction: f32, halt_on_collision: bool) -> Self {
+        CollisionDetector {
+            close_call_fraction,
+            halt_on_collision,
+            active: HashMap::new(),
+        }
+    }
+
+    pub fn halt_on_collision(&self) -> bool {
+        self.halt_on_collision
+    }
+
+    // Check every pair of neighboring vehicles and return the contacts that are new
+    // since the last call. `grid` must be built from the current `vehicles`.
+    pub fn detect(&mut self, vehicles: &[Vehicle], grid: &SpatialGrid, safety_distance: f32, time: Duration) -> Vec<Contact> {
+        let threshold = safety_distance * self.close_call_fraction;
+        let max_size = vehicles.iter().map(|v| v.size).fold(0.0, f32::max);
+        let mut current = HashMap::new();
+        let mut contacts = Vec::new();
+
+        for (i, a) in vehicles.iter().enumerate() {
+            // Rotated footprints reach up to half a diagonal from their center
+            for j in grid.query(a.position, max_size * std::f32::consts::SQRT_2 + threshold) {
+                if j <= i {
+                    continue;
+                }
+                let b = &vehicles[j];
+                let kind = match contact_kind(a, b, threshold) {
+                    Some(kind) => kind,
+                    None => continue,
+                };
+                let (first, second) = if a.id < b.id { (a, b) } else { (b, a) };
+                let pair = (first.id, second.id);
+                current.insert(pair, kind);
+
+                let is_new = match self.active.get(&pair) {
+                    None => true,
+                    Some(ContactKind::CloseCall) => kind == ContactKind::Collision,
+                    Some(ContactKind::Collision) => false,
+                };
+                if is_new {
+                    contacts.push(Contact {
+                        kind,
+                        vehicle_ids: pair,
+                        positions: (first.position, second.position),
+                        time,
+                    });
+                }
+            }
+        }
+
+        // Keep a collision latched while the pair is still touching
+        for (pair, kind) in current.iter_mut() {
+            if self.active.get(pair) == Some(&ContactKind::Collision) {
+                *kind = ContactKind::Collision;
+            }
+        }
+        self.active = current;
+        contacts
+    }
+}
+
+impl Default for CollisionDetector {
+    fn default() -> Self {
+        CollisionDetector::new(0.5, false)
+    }
+}
+
+// Vehicle footprints are squares rotated by their heading. The separating axis
+// test gives the largest separation along the four edge normals, which is zero
+// or negative when the footprints overlap and otherwise a lower bound of the gap.
+pub fn footprint_separation(corners_a: &[Position; 4], heading_a: f32, corners_b: &[Position; 4], heading_b: f32) -> f32 {
+    let (ax, ay) = (heading_a.cos(), heading_a.sin());
+    let (bx, by) = (heading_b.cos(), heading_b.sin());
+    let axes = [(ax, ay), (-ay, ax), (bx, by), (-by, bx)];
+
+    axes.iter()
+        .map(|&axis| {
+            let (min_a, max_a) = project(corners_a, axis);
+            let (min_b, max_b) = project(corners_b, axis);
+            (min_b - max_a).max(min_a - max_b)
+        })
+        .fold(f32::MIN, f32::max)
+}
+
+fn contact_kind(a: &Vehicle, b: &Vehicle, close_call_threshold: f32) -> Option<ContactKind> {
+    let separation = footprint_separation(&a.corners(), a.heading, &b.corners(), b.heading);
+
+    if separation <= 0.0 {
+        Some(ContactKind::Collision)
+    } else if separation < close_call_threshold {
+        Some(ContactKind::CloseCall)
+    } else {
+        None
+    }
+}
+
+// Extent of the corners along the axis, as (min, max)
+pub(crate) fn project(corners: &[Position; 4], (axis_x, axis_y): (f32, f32)) -> (f32, f32) {
+    corners
+        .iter()
+        .map(|corner| corner.x * axis_x + corner.y * axis_y)
+        .fold((f32::MAX, f32::MIN), |(min, max), value| (min.min(value), max.max(value)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vehicle::Lane;
+    use crate::MovementDirection;
+    use crate::TurnDirection;
+
+    const SAFETY_DISTANCE: f32 = 10.0;
+
+    fn vehicle(id: i32, x: f32, y: f32) -> Vehicle {
+        let mut vehicle =
+            Vehicle::new(MovementDirection::Right, TurnDirection::Straight, 600.0, Position::new(x, y), Lane::Middle);
+        vehicle.id = id;
+        vehicle
+    }
+
+    fn detect(
+        detector: &mut CollisionDetector,
+        vehicles: &[Vehicle],
+        cell_size: f32,
+    ) -> Vec<(ContactKind, (i32, i32))> {
+        let mut grid = SpatialGrid::new(cell_size);
+        grid.rebuild(vehicles, cell_size);
+        detector
+            .detect(vehicles, &grid, SAFETY_DISTANCE, Duration::ZERO)
+            .iter()
+            .map(|contact| (contact.kind, contact.vehicle_ids))
+            .collect()
+    }
+
+    #[test]
+    fn footprints_are_classified_by_gap() {
+        // Vehicles are 55 wide; the close-call threshold is half the safety distance
+        let cases = [
+            (50.0, Some(ContactKind::Collision)),
+            (55.0, Some(ContactKind::Collision)),
+            (58.0, Some(ContactKind::CloseCall)),
+            (61.0, None),
+        ];
+        for (distance, expected) in cases {
+            let mut detector = CollisionDetector::default();
+            let found = detect(&mut detector, &[vehicle(1, 0.0, 0.0), vehicle(2, distance, 0.0)], 100.0);
+            assert_eq!(found.first().map(|&(kind, _)| kind), expected, "{} apart", distance);
+        }
+    }
+
+    #[test]
+    fn rotated_footprints_reach_into_neighbouring_cells() {
+        // Centers 60 apart in cells much smaller than a vehicle. Side by side the
+        // squares would be clear of each other, but turned by 45 degrees a corner
+        // of the first reaches into the second.
+        let mut a = vehicle(1, 99.0, 99.0);
+        let b = vehicle(2, 159.0, 99.0);
+        let mut detector = CollisionDetector::default();
+        assert!(detect(&mut detector, &[vehicle(1, 99.0, 99.0), vehicle(2, 159.0, 99.0)], 20.0).is_empty());
+        a.heading = std::f32::consts::FRAC_PI_4;
+        assert_eq!(detect(&mut detector, &[a, b], 20.0), vec![(ContactKind::Collision, (1, 2))]);
+    }
+
+    #[test]
+    fn lasting_contacts_are_reported_once() {
+        let mut detector = CollisionDetector::default();
+        let close = [vehicle(2, 58.0, 0.0), vehicle(1, 0.0, 0.0)];
+        assert_eq!(detect(&mut detector, &close, 100.0), vec![(ContactKind::CloseCall, (1, 2))]);
+        assert!(detect(&mut detector, &close, 100.0).is_empty());
+
+        // Escalating to a collision is new, staying in it is not
+        let touching = [vehicle(2, 50.0, 0.0), vehicle(1, 0.0, 0.0)];
+        assert_eq!(detect(&mut detector, &touching, 100.0), vec![(ContactKind::Collision, (1, 2))]);
+        assert!(detect(&mut detector, &close, 100.0).is_empty());
+
+        // Once apart, a new contact is reported again
+        assert!(detect(&mut detector, &[vehicle(2, 200.0, 0.0), vehicle(1, 0.0, 0.0)], 100.0).is_empty());
+        assert_eq!(detect(&mut detector, &close, 100.0), vec![(ContactKind::CloseCall, (1, 2))]);
+    }
+}
@@ -0,0 +1,203 @@
+use crate::collision::footprint_separation;
+use crate::demand::{parse_movement_direction, parse_turn_direction};
+use crate::geometry::Geometry;
+use crate::path::turning_path_from;
+use crate::spawner::{APPROACHES, TURNS};
+use crate::vehicle::footprint;
+use crate::MovementDirection;
+use crate::Position;
+use crate::TurnDirection;
+
+// One of the ways through an intersection: the approach and the turn taken from it
+pub type Movement = (MovementDirection, TurnDirection);
+
+pub const MOVEMENT_COUNT: usize = APPROACHES.len() * TURNS.len();
+
+// Footprints are this much narrower than a lane, so vehicles side by side in
+// neighbouring lanes do not count as conflicting through rounding
+const PARALLEL_TOLERANCE: f32 = 0.5;
+
+// A footprint at one point of a sampled path, with its heading
+type Sample = ([Position; 4], f32);
+
+// Which pairs of movements can get in each other's way inside the box. Pairs
+// that cannot never need their tile claims compared.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConflictMatrix {
+    conflicts: [[bool; MOVEMENT_COUNT]; MOVEMENT_COUNT],
+}
+
+impl ConflictMatrix {
+    // Two movements conflict when vehicles as wide as a lane following their
+    // paths would overlap anywhere in the box, for any of the exit lanes a route
+    // may ask for
+    pub fn from_geometry(geometry: &Geometry) -> Self {
+        let step = geometry.lane_width / 4.0;
+        let size = geometry.lane_width - PARALLEL_TOLERANCE;
+        let paths: Vec<Vec<Vec<Sample>>> = movements()
+            .map(|(movement_direction, turn_direction)| {
+                let entry = geometry.entry_point(movement_direction, turn_direction);
+                (0..geometry.lanes_per_approach)
+                    .map(|exit_lane| {
+                        let path = turning_path_from(geometry, entry, movement_direction, turn_direction, exit_lane);
+                        let samples = (path.length() / step).ceil() as usize;
+                        (0..=samples)
+                            .map(|i| {
+                                let (position, heading) = path.sample(i as f32 * step);
+                                (footprint(position, heading, size), heading)
+                            })
+                            .collect()
+                    })
+                    .collect()
+            })
+            .collect();
+
+        let mut conflicts = [[false; MOVEMENT_COUNT]; MOVEMENT_COUNT];
+        for first in 0..MOVEMENT_COUNT {
+            for second in first..MOVEMENT_COUNT {
+                let conflict = paths[first].iter().any(|a| paths[second].iter().any(|b| overlap(a, b)));
+                conflicts[first][second] = conflict;
+                conflicts[second][first] = conflict;
+            }
+        }
+        ConflictMatrix { conflicts }
+    }
+
+    pub fn conflicts(&self, first: Movement, second: Movement) -> bool {
+        self.conflicts[movement_index(first)][movement_index(second)]
+    }
+
+    // Override the entry for a pair of movements, in both orders
+    pub fn set(&mut self, first: Movement, second: Movement, conflict: bool) {
+        let (first, second) = (movement_index(first), movement_index(second));
+        self.conflicts[first][second] = conflict;
+        self.conflicts[second][first] = conflict;
+    }
+
+    // The matrix as text, one row and column per movement labelled by the
+    // initials of approach and turn ("UL" is up, left), 'x' marking conflicts
+    pub fn to_table(&self) -> String {
+        let labels: Vec<String> = movements().map(movement_label).collect();
+        let mut table = format!("   {}\n", labels.join(" "));
+        for (row, label) in labels.iter().enumerate() {
+            let cells: Vec<&str> = (0..MOVEMENT_COUNT)
+                .map(|column| if self.conflicts[row][column] { " x" } else { " ." })
+                .collect();
+            table += &format!("{} {}\n", label, cells.join(" "));
+        }
+        table
+    }
+}
+
+// All movements, approach by approach in `APPROACHES` order
+pub fn movements() -> impl Iterator<Item = Movement> {
+    APPROACHES
+        .into_iter()
+        .flat_map(|movement_direction| TURNS.into_iter().map(move |turn_direction| (movement_direction, turn_direction)))
+}
+
+fn movement_index((movement_direction, turn_direction): Movement) -> usize {
+    let approach = APPROACHES.iter().position(|&approach| approach == movement_direction).unwrap();
+    let turn = TURNS.iter().position(|&turn| turn == turn_direction).unwrap();
+    approach * TURNS.len() + turn
+}
+
+fn movement_label((movement_direction, turn_direction): Movement) -> String {
+    let initial = |name: String| name[..1].to_uppercase();
+    initial(format!("{:?}", movement_direction)) + &initial(format!("{:?}", turn_direction))
+}
+
+// Movement written as "<approach> <turn>", e.g. "up left"
+pub fn movement_name((movement_direction, turn_direction): Movement) -> String {
+    format!("{:?} {:?}", movement_direction, turn_direction).to_lowercase()
+}
+
+pub fn parse_movement(name: &str) -> Option<Movement> {
+    let mut words = name.split_whitespace();
+    let movement_direction = parse_movement_direction(words.next()?)?;
+    let turn_direction = parse_turn_direction(words.next()?)?;
+    match words.next() {
+        Some(_) => None,
+        None => Some((movement_direction, turn_direction)),
+    }
+}
+
+// True when any footprint along one sampled path overlaps any along the other
+fn overlap(first: &[Sample], second: &[Sample]) -> bool {
+    first.iter().any(|(corners_a, heading_a)| {
+        second
+            .iter()
+            .any(|(corners_b, heading_b)| footprint_separation(corners_a, *heading_a, corners_b, *heading_b) <= 0.0)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const UP: MovementDirection = MovementDirection::Up;
+    const DOWN: MovementDirection = MovementDirection::Down;
+    const LEFT: MovementDirection = MovementDirection::Left;
+    const RIGHT: MovementDirection = MovementDirection::Right;
+
+    fn matrix() -> ConflictMatrix {
+        ConflictMatrix::from_geometry(&Geometry::default())
+    }
+
+    #[test]
+    fn opposite_right_turns_do_not_conflict() {
+        let matrix = matrix();
+        assert!(!matrix.conflicts((UP, TurnDirection::Right), (DOWN, TurnDirection::Right)));
+        assert!(!matrix.conflicts((LEFT, TurnDirection::Right), (RIGHT, TurnDirection::Right)));
+        assert!(!matrix.conflicts((UP, TurnDirection::Straight), (DOWN, TurnDirection::Straight)));
+    }
+
+    #[test]
+    fn crossing_movements_conflict() {
+        let matrix = matrix();
+        assert!(matrix.conflicts((UP, TurnDirection::Straight), (LEFT, TurnDirection::Straight)));
+        assert!(matrix.conflicts((UP, TurnDirection::Left), (DOWN, TurnDirection::Straight)));
+        for movement in movements() {
+            assert!(matrix.conflicts(movement, movement), "{} with itself", movement_name(movement));
+        }
+    }
+
+    #[test]
+    fn matrix_is_symmetric() {
+        let matrix = matrix();
+        for first in movements() {
+            for second in movements() {
+                assert_eq!(matrix.conflicts(first, second), matrix.conflicts(second, first));
+            }
+        }
+    }
+
+    #[test]
+    fn set_overrides_both_orders() {
+        let mut overridden = matrix();
+        let (up_right, down_right) = ((UP, TurnDirection::Right), (DOWN, TurnDirection::Right));
+        overridden.set(down_right, up_right, true);
+        assert!(overridden.conflicts(up_right, down_right));
+        assert!(overridden.conflicts(down_right, up_right));
+
+        overridden.set(up_right, down_right, false);
+        assert_eq!(overridden, matrix());
+    }
+
+    #[test]
+    fn movement_names_round_trip() {
+        for movement in movements() {
+            assert_eq!(parse_movement(&movement_name(movement)), Some(movement));
+        }
+        assert_eq!(parse_movement("Left  straight"), Some((LEFT, TurnDirection::Straight)));
+        assert_eq!(parse_movement("up"), None);
+        assert_eq!(parse_movement("up left now"), None);
+    }
+
+    #[test]
+    fn table_has_a_row_per_movement() {
+        let table = matrix().to_table();
+        assert_eq!(table.lines().count(), MOVEMENT_COUNT + 1);
+        assert!(table.lines().nth(1).unwrap().starts_with(&movement_label((APPROACHES[0], TURNS[0]))));
+    }
+}
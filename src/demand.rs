@@ -0,0 +1,302 @@
+use crate::spawner::{APPROACHES, TURNS};
+use crate::MovementDirection;
+use crate::TurnDirection;
+use rand::Rng;
+use std::collections::HashMap;
+use std::time::Duration;
+
+// Constant arrival rate over [start, end), in vehicles per second
+#[derive(Debug, Clone, PartialEq)]
+pub struct RatePeriod {
+    pub start: Duration,
+    pub end: Duration,
+    pub rate: f32,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ArrivalRate {
+    // Homogeneous Poisson process, vehicles per second
+    Constant(f32),
+    // Time-of-day curve; outside every period the rate is zero
+    Piecewise(Vec<RatePeriod>),
+}
+
+impl ArrivalRate {
+    pub fn rate_at(&self, time: Duration) -> f32 {
+        match self {
+            ArrivalRate::Constant(rate) => *rate,
+            ArrivalRate::Piecewise(periods) => periods
+                .iter()
+                .find(|period| period.start <= time && time < period.end)
+                .map_or(0.0, |period| period.rate),
+        }
+    }
+}
+
+// Columns of a demand CSV file, see `DemandProfile::from_csv`
+const CSV_COLUMNS: [&str; 5] = ["start", "end", "approach", "turn", "count"];
+
+// Arrival processes per approach and turn, sampled once per tick by the simulation
+#[derive(Debug, Clone, Default)]
+pub struct DemandProfile {
+    rates: HashMap<(MovementDirection, TurnDirection), ArrivalRate>,
+}
+
+impl DemandProfile {
+    pub fn new() -> Self {
+        DemandProfile::default()
+    }
+
+    pub fn set_rate(&mut self, movement_direction: MovementDirection, turn_direction: TurnDirection, rate: ArrivalRate) {
+        self.rates.insert((movement_direction, turn_direction), rate);
+    }
+
+    // Time-of-day rate from a list of periods in any order; fails if two of them overlap
+    pub fn set_periods(
+        &mut self,
+        movement_direction: MovementDirection,
+        turn_direction: TurnDirection,
+        mut periods: Vec<RatePeriod>,
+    ) -> Result<(), String> {
+        periods.sort_by_key(|period| period.start);
+        if let Some(pair) = periods.windows(2).find(|pair| pair[1].start < pair[0].end) {
+            return Err(format!(
+                "overlapping periods for {:?} {:?} starting at {} s and {} s",
+                movement_direction,
+                turn_direction,
+                pair[0].start.as_secs_f64(),
+                pair[1].start.as_secs_f64()
+            ));
+        }
+        self.set_rate(movement_direction, turn_direction, ArrivalRate::Piecewise(periods));
+        Ok(())
+    }
+
+    pub fn rate(&self, movement_direction: MovementDirection, turn_direction: TurnDirection) -> Option<&ArrivalRate> {
+        self.rates.get(&(movement_direction, turn_direction))
+    }
+
+    // Every lane with an arrival process, in the fixed order used by `arrivals`
+    pub fn rates(&self) -> impl Iterator<Item = (MovementDirection, TurnDirection, &ArrivalRate)> + '_ {
+        APPROACHES.into_iter().flat_map(move |movement_direction| {
+            TURNS.into_iter().filter_map(move |turn_direction| {
+                self.rate(movement_direction, turn_direction).map(|rate| (movement_direction, turn_direction, rate))
+            })
+        })
+    }
+
+    // Draw the arrivals of one tick of length `dt` starting at `time`. Lanes are
+    // visited in a fixed order so the result only depends on the RNG state.
+    pub fn arrivals(&self, time: Duration, dt: Duration, rng: &mut impl Rng) -> Vec<(MovementDirection, TurnDirection)> {
+        let mut arrivals = Vec::new();
+        for movement_direction in APPROACHES {
+            for turn_direction in TURNS {
+                let rate = match self.rate(movement_direction, turn_direction) {
+                    Some(rate) => rate.rate_at(time),
+                    None => continue,
+                };
+                for _ in 0..poisson(rate * dt.as_secs_f32(), rng) {
+                    arrivals.push((movement_direction, turn_direction));
+                }
+            }
+        }
+        arrivals
+    }
+
+    pub fn load_csv(path: &str) -> Result<Self, String> {
+        let text = std::fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+        Self::from_csv(&text).map_err(|e| format!("{}: {}", path, e))
+    }
+
+    // Parse vehicle counts in the form `start,end,approach,turn,count` where
+    // `start` and `end` are seconds of simulation time, `approach` is the
+    // movement direction (up, down, left, right) and `turn` is left, straight
+    // or right. Each row becomes a period with rate count / (end - start).
+    // Blank lines, `#` comments and a leading header row naming these columns
+    // are ignored.
+    pub fn from_csv(text: &str) -> Result<Self, String> {
+        let mut periods: HashMap<(MovementDirection, TurnDirection), Vec<RatePeriod>> = HashMap::new();
+        let mut first_row = true;
+
+        for (index, line) in text.lines().enumerate() {
+            let line_number = index + 1;
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let fields: Vec<&str> = line.split(',').map(str::trim).collect();
+            let is_header = fields.len() == CSV_COLUMNS.len()
+                && fields.iter().zip(CSV_COLUMNS).all(|(field, column)| field.eq_ignore_ascii_case(column));
+            if std::mem::take(&mut first_row) && is_header {
+                continue;
+            }
+            if fields.len() != CSV_COLUMNS.len() {
+                return Err(format!(
+                    "line {}: expected {} fields, found {}",
+                    line_number,
+                    CSV_COLUMNS.len(),
+                    fields.len()
+                ));
+            }
+
+            let start = parse_seconds(fields[0], "start", line_number)?;
+            let end = parse_seconds(fields[1], "end", line_number)?;
+            if end <= start {
+                return Err(format!("line {}: end must be after start", line_number));
+            }
+            let movement_direction = parse_movement_direction(fields[2])
+                .ok_or_else(|| format!("line {}: unknown approach '{}'", line_number, fields[2]))?;
+            let turn_direction = parse_turn_direction(fields[3])
+                .ok_or_else(|| format!("line {}: unknown turn '{}'", line_number, fields[3]))?;
+            let count: u32 = fields[4]
+                .parse()
+                .map_err(|_| format!("line {}: invalid count '{}'", line_number, fields[4]))?;
+
+            let rate = count as f32 / (end - start).as_secs_f32();
+            periods.entry((movement_direction, turn_direction)).or_default().push(RatePeriod { start, end, rate });
+        }
+
+        // Lanes in a fixed order, so the same file always reports the same error
+        let mut profile = DemandProfile::new();
+        for movement_direction in APPROACHES {
+            for turn_direction in TURNS {
+                if let Some(lane_periods) = periods.remove(&(movement_direction, turn_direction)) {
+                    profile.set_periods(movement_direction, turn_direction, lane_periods)?;
+                }
+            }
+        }
+        Ok(profile)
+    }
+}
+
+fn parse_seconds(field: &str, name: &str, line_number: usize) -> Result<Duration, String> {
+    field
+        .parse::<f32>()
+        .ok()
+        .and_then(|seconds| Duration::try_from_secs_f32(seconds).ok())
+        .ok_or_else(|| format!("line {}: invalid {} time '{}'", line_number, name, field))
+}
+
+pub fn parse_movement_direction(name: &str) -> Option<MovementDirection> {
+    match name.to_ascii_lowercase().as_str() {
+        "up" => Some(MovementDirection::Up),
+        "down" => Some(MovementDirection::Down),
+        "left" => Some(MovementDirection::Left),
+        "right" => Some(MovementDirection::Right),
+        _ => None,
+    }
+}
+
+pub fn parse_turn_direction(name: &str) -> Option<TurnDirection> {
+    match name.to_ascii_lowercase().as_str() {
+        "left" => Some(TurnDirection::Left),
+        "straight" => Some(TurnDirection::Straight),
+        "right" => Some(TurnDirection::Right),
+        _ => None,
+    }
+}
+
+// Number of events of a Poisson distribution with mean `mean` (Knuth's method,
+// fine for the small per-tick means used here)
+fn poisson(mean: f32, rng: &mut impl Rng) -> u32 {
+    if mean <= 0.0 {
+        return 0;
+    }
+    let limit = (-mean).exp();
+    let mut count = 0;
+    let mut product: f32 = rng.gen();
+    while product > limit {
+        count += 1;
+        product *= rng.gen::<f32>();
+    }
+    count
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn from_csv_reads_counts_as_rates() {
+        let text = "start,end,approach,turn,count\n\
+                    # morning peak\n\
+                    0,60,up,left,30\n\
+                    \n\
+                    60,120,Up,Left,6\n\
+                    0,10,right,straight,5\n";
+        let profile = DemandProfile::from_csv(text).unwrap();
+
+        let up_left = profile.rate(MovementDirection::Up, TurnDirection::Left).unwrap();
+        assert_eq!(up_left.rate_at(Duration::from_secs(30)), 0.5);
+        assert_eq!(up_left.rate_at(Duration::from_secs(90)), 0.1);
+        assert_eq!(up_left.rate_at(Duration::from_secs(120)), 0.0);
+        let right_straight = profile.rate(MovementDirection::Right, TurnDirection::Straight).unwrap();
+        assert_eq!(right_straight.rate_at(Duration::from_secs(5)), 0.5);
+        assert!(profile.rate(MovementDirection::Down, TurnDirection::Right).is_none());
+    }
+
+    #[test]
+    fn from_csv_rejects_bad_rows() {
+        let cases = [
+            ("0,60,up,left\n", "line 1: expected 5 fields, found 4"),
+            ("0,60,up,left,3\nsoon,60,up,left,3\n", "line 2: invalid start time 'soon'"),
+            ("0,-1,up,left,3\n", "line 1: invalid end time '-1'"),
+            ("60,60,up,left,3\n", "line 1: end must be after start"),
+            ("0,60,north,left,3\n", "line 1: unknown approach 'north'"),
+            ("0,60,up,back,3\n", "line 1: unknown turn 'back'"),
+            ("0,60,up,left,2.5\n", "line 1: invalid count '2.5'"),
+        ];
+        for (text, message) in cases {
+            assert_eq!(DemandProfile::from_csv(text).unwrap_err(), message, "{:?}", text);
+        }
+    }
+
+    #[test]
+    fn from_csv_only_skips_a_real_header() {
+        let header = DemandProfile::from_csv(" Start , END,approach,turn,count\n0,60,up,left,3\n").unwrap();
+        assert!(header.rate(MovementDirection::Up, TurnDirection::Left).is_some());
+
+        // A typo in the first data row is reported, not taken for a header
+        assert_eq!(DemandProfile::from_csv("O,60,up,left,3\n").unwrap_err(), "line 1: invalid start time 'O'");
+        assert_eq!(
+            DemandProfile::from_csv("begin,end,approach,turn,count\n").unwrap_err(),
+            "line 1: invalid start time 'begin'"
+        );
+        // Only the first row may be a header
+        assert_eq!(
+            DemandProfile::from_csv("0,60,up,left,3\nstart,end,approach,turn,count\n").unwrap_err(),
+            "line 2: invalid start time 'start'"
+        );
+    }
+
+    #[test]
+    fn from_csv_reports_the_first_invalid_lane() {
+        let text = "0,60,right,right,1\n30,90,right,right,1\n\
+                    0,60,up,straight,1\n30,90,up,straight,1\n\
+                    0,60,up,left,1\n30,90,up,left,1\n";
+        for _ in 0..10 {
+            let error = DemandProfile::from_csv(text).unwrap_err();
+            assert!(error.starts_with("overlapping periods for Up Left"), "{}", error);
+        }
+    }
+
+    #[test]
+    fn from_csv_rejects_overlapping_periods() {
+        let text = "30,90,down,right,10\n0,60,down,right,10\n90,120,down,right,10\n";
+        let error = DemandProfile::from_csv(text).unwrap_err();
+        assert_eq!(error, "overlapping periods for Down Right starting at 0 s and 30 s");
+    }
+
+    #[test]
+    fn adjacent_periods_do_not_overlap() {
+        let mut profile = DemandProfile::new();
+        let periods = vec![
+            RatePeriod { start: Duration::from_secs(10), end: Duration::from_secs(20), rate: 2.0 },
+            RatePeriod { start: Duration::ZERO, end: Duration::from_secs(10), rate: 1.0 },
+        ];
+        profile.set_periods(MovementDirection::Left, TurnDirection::Straight, periods).unwrap();
+
+        let rate = profile.rate(MovementDirection::Left, TurnDirection::Straight).unwrap();
+        assert_eq!(rate.rate_at(Duration::from_secs(10)), 2.0);
+    }
+}
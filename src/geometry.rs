@@ -0,0 +1,182 @@
+use crate::path::{direction_vector, exit_direction};
+use crate::vehicle::Lane;
+use crate::MovementDirection;
+use crate::Position;
+use crate::TurnDirection;
+
+// Layout of a single four-way intersection. Every position used for spawning,
+// turning, distance computation and drawing is derived from these values.
+//
+// Traffic drives on the right. Each approach has `lanes_per_approach` lanes of
+// `lane_width` to the right of the road center line; the innermost lane is used
+// by left turns and the outermost by right turns.
+#[derive(Debug, Clone)]
+pub struct Geometry {
+    pub center: Position,
+    pub lane_width: f32,
+    pub lanes_per_approach: usize,
+    pub world_width: f32,
+    pub world_height: f32,
+    // How far outside the world vehicles are spawned, so they drive in smoothly
+    pub spawn_margin: f32,
+}
+
+impl Default for Geometry {
+    fn default() -> Self {
+        Geometry {
+            center: Position::new(9.0 * 56.0, 9.0 * 56.0),
+            lane_width: 56.0,
+            lanes_per_approach: 3,
+            world_width: 1000.0,
+            world_height: 1000.0,
+            spawn_margin: 67.0,
+        }
+    }
+}
+
+impl Geometry {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.lanes_per_approach == 0 {
+            return Err("lanes_per_approach must be at least 1".to_string());
+        }
+        if self.lane_width.is_nan() || self.lane_width <= 0.0 {
+            return Err(format!("lane_width must be positive, got {}", self.lane_width));
+        }
+        if self.world_width.is_nan() || self.world_height.is_nan() || self.world_width <= 0.0 || self.world_height <= 0.0 {
+            return Err(format!("world size must be positive, got {}x{}", self.world_width, self.world_height));
+        }
+        let (min, max) = self.intersection_bounds();
+        if min.x < 0.0 || min.y < 0.0 || max.x > self.world_width || max.y > self.world_height {
+            return Err("intersection box must lie inside the world".to_string());
+        }
+        Ok(())
+    }
+
+    // Distance from the road center line to its outer edge
+    pub fn road_half_width(&self) -> f32 {
+        self.lane_width * self.lanes_per_approach as f32
+    }
+
+    // Top-left and bottom-right corners of the intersection box
+    pub fn intersection_bounds(&self) -> (Position, Position) {
+        let half = self.road_half_width();
+        (
+            Position::new(self.center.x - half, self.center.y - half),
+            Position::new(self.center.x + half, self.center.y + half),
+        )
+    }
+
+    // Lane used for a turn, counted from the center line outwards
+    pub fn lane_index(&self, turn_direction: TurnDirection) -> usize {
+        match turn_direction {
+            TurnDirection::Left => 0,
+            TurnDirection::Straight => self.lanes_per_approach / 2,
+            TurnDirection::Right => self.lanes_per_approach - 1,
+        }
+    }
+
+    // True when a vehicle in lane `lane_index` may perform `turn_direction`
+    pub fn lane_allows(&self, lane_index: usize, turn_direction: TurnDirection) -> bool {
+        self.lane_index(turn_direction) == lane_index
+    }
+
+    // Lane of the road travelled in `movement_direction` containing `position`,
+    // None when the position is not on that side of the road
+    pub fn lane_at(&self, movement_direction: MovementDirection, position: Position) -> Option<usize> {
+        let (dx, dy) = direction_vector(movement_direction);
+        let (rx, ry) = (-dy, dx);
+        let offset = (position.x - self.center.x) * rx + (position.y - self.center.y) * ry;
+        if offset < 0.0 || offset >= self.road_half_width() {
+            return None;
+        }
+        Some((offset / self.lane_width) as usize)
+    }
+
+    // Point on the center of lane `lane_index` of the road travelled in
+    // `movement_direction`, at longitudinal coordinate `longitudinal`
+    pub fn lane_point(&self, movement_direction: MovementDirection, lane_index: usize, longitudinal: f32) -> Position {
+        let (dx, dy) = direction_vector(movement_direction);
+        let (rx, ry) = (-dy, dx); // right-hand side of the direction of travel
+        let offset = (lane_index as f32 + 0.5) * self.lane_width;
+        let along = longitudinal - (self.center.x * dx + self.center.y * dy);
+        Position::new(
+            self.center.x + rx * offset + dx * along,
+            self.center.y + ry * offset + dy * along,
+        )
+    }
+
+    // Longitudinal coordinates, along `movement_direction`, of the box edge a
+    // vehicle enters through (the stop line) and of the edge it leaves through
+    pub fn intersection_extent(&self, movement_direction: MovementDirection) -> (f32, f32) {
+        let (min, max) = self.intersection_bounds();
+        project_extent(min, max, movement_direction)
+    }
+
+    // Longitudinal coordinates of the world edges a vehicle drives in and out through
+    pub fn world_extent(&self, movement_direction: MovementDirection) -> (f32, f32) {
+        project_extent(
+            Position::new(0.0, 0.0),
+            Position::new(self.world_width, self.world_height),
+            movement_direction,
+        )
+    }
+
+    // Entry position and lane for a vehicle arriving from the given approach
+    pub fn spawn_point(&self, movement_direction: MovementDirection, turn_direction: TurnDirection) -> (Position, Lane) {
+        let (world_entry, _) = self.world_extent(movement_direction);
+        let position = self.lane_point(
+            movement_direction,
+            self.lane_index(turn_direction),
+            world_entry - self.spawn_margin,
+        );
+        (position, Lane::for_turn(turn_direction))
+    }
+
+    // Point where the lane used for `turn_direction` crosses the stop line
+    pub fn entry_point(&self, movement_direction: MovementDirection, turn_direction: TurnDirection) -> Position {
+        let (entry, _) = self.intersection_extent(movement_direction);
+        self.lane_point(movement_direction, self.lane_index(turn_direction), entry)
+    }
+
+    // Point where a vehicle leaves the box onto the lane of the same turn on its exit road
+    pub fn exit_point(&self, movement_direction: MovementDirection, turn_direction: TurnDirection) -> Position {
+        self.exit_point_in_lane(movement_direction, turn_direction, self.lane_index(turn_direction))
+    }
+
+    // Same as `exit_point`, leaving onto an arbitrary lane of the exit road
+    pub fn exit_point_in_lane(&self, movement_direction: MovementDirection, turn_direction: TurnDirection, lane_index: usize) -> Position {
+        let exit = exit_direction(movement_direction, turn_direction);
+        let (_, exit_edge) = self.intersection_extent(exit);
+        self.lane_point(exit, lane_index, exit_edge)
+    }
+
+    // Stop line across all lanes of an approach, from the center line to the road edge
+    pub fn stop_line(&self, movement_direction: MovementDirection) -> (Position, Position) {
+        let (entry, _) = self.intersection_extent(movement_direction);
+        let (dx, dy) = direction_vector(movement_direction);
+        let (rx, ry) = (-dy, dx);
+        // Lane points are lane centers, move out by half a lane to reach the edges
+        let inner = self.lane_point(movement_direction, 0, entry);
+        let outer = self.lane_point(movement_direction, self.lanes_per_approach - 1, entry);
+        let half_lane = self.lane_width / 2.0;
+        (
+            Position::new(inner.x - rx * half_lane, inner.y - ry * half_lane),
+            Position::new(outer.x + rx * half_lane, outer.y + ry * half_lane),
+        )
+    }
+
+    // True once a vehicle travelling in `movement_direction` has driven past the far world edge
+    pub fn has_left_world(&self, position: Position, movement_direction: MovementDirection) -> bool {
+        let (dx, dy) = direction_vector(movement_direction);
+        let (_, world_exit) = self.world_extent(movement_direction);
+        position.x * dx + position.y * dy > world_exit
+    }
+}
+
+// Smallest and largest projection of an axis-aligned box onto a cardinal direction
+fn project_extent(min: Position, max: Position, movement_direction: MovementDirection) -> (f32, f32) {
+    let (dx, dy) = direction_vector(movement_direction);
+    let a = min.x * dx + min.y * dy;
+    let b = max.x * dx + max.y * dy;
+    (a.min(b), a.max(b))
+}
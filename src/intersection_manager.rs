@@ -1,7 +1,50 @@
 use crate::TurnDirection;
+use crate::conflict_matrix::ConflictMatrix;
+use crate::geometry::Geometry;
+use crate::path::direction_vector;
+use crate::policy::{IntersectionPolicy, PolicyContext, PolicyKind};
+use crate::tiles::{TileConfig, TileGrid, TileSlot};
 use crate::vehicle::*;
 use crate::MovementDirection;
+use crate::Position;
+use std::collections::HashSet;
 
+// When a request may take over the slots of granted reservations it conflicts
+// with. The vehicles losing theirs are told through `take_revocations`.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum PreemptionRule {
+    // Granted reservations are binding
+    Never,
+    // A vehicle of higher priority, like an emergency vehicle, preempts vehicles of
+    // lower priority that have not reached the box yet
+    HigherPriority,
+}
+
+impl PreemptionRule {
+    pub const ALL: [PreemptionRule; 2] = [PreemptionRule::Never, PreemptionRule::HigherPriority];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            PreemptionRule::Never => "never",
+            PreemptionRule::HigherPriority => "higher_priority",
+        }
+    }
+
+    pub fn parse(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|rule| rule.name() == name.to_ascii_lowercase())
+    }
+}
+
+// Where a vehicle is relative to the intersection box along its path
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy)]
+pub enum IntersectionPhase {
+    Approaching,
+    Inside,
+    Exited,
+}
+
+// Reservation of a vehicle's crossing; `start_time` and `end_time` are absolute
+// simulation clock values bounding the (tile, slot) pairs in `tiles`
 //#[derive(Debug)]
 pub struct Reservation {
     pub vehicle_id: i32,
@@ -11,124 +54,231 @@ pub struct Reservation {
 
     pub start_time: std::time::Duration,
     pub end_time: std::time::Duration,
+    pub tiles: HashSet<TileSlot>,
+    pub priority: u32,
 }
 
+// Reservations a manager currently holds, and how many ended so far. Expired
+// ones ran their course, because their window passed or the vehicle left the
+// box; cancelled ones were given up by their vehicle before that and revoked
+// ones were taken away by a preempting request.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ReservationCounts {
+    pub active: usize,
+    pub expired: usize,
+    pub cancelled: usize,
+    pub revoked: usize,
+}
 
 pub struct IntersectionManager {
-    reservations: Vec<Reservation>,
+    context: PolicyContext,
+    policy: Box<dyn IntersectionPolicy>,
+}
+
+impl Default for IntersectionManager {
+    fn default() -> Self {
+        IntersectionManager::new()
+    }
 }
 
 
 impl IntersectionManager {
     pub fn new() -> Self {
+        IntersectionManager::with_geometry(Geometry::default())
+    }
+
+    pub fn with_geometry(geometry: Geometry) -> Self {
         IntersectionManager {
-            reservations: Vec::new(),
+            context: PolicyContext {
+                tile_grid: TileGrid::new(&geometry, TileConfig::default()),
+                conflict_matrix: ConflictMatrix::from_geometry(&geometry),
+                preemption_rule: PreemptionRule::Never,
+                geometry,
+            },
+            policy: PolicyKind::Fcfs.build(),
         }
     }
+
+    pub fn geometry(&self) -> &Geometry {
+        &self.context.geometry
+    }
+
+    pub fn policy_kind(&self) -> PolicyKind {
+        self.policy.kind()
+    }
+
+    // Switch the control algorithm; meant to be called before any reservation is made
+    pub fn set_policy(&mut self, kind: PolicyKind) {
+        self.policy = kind.build();
+    }
+
+    pub fn tile_grid(&self) -> &TileGrid {
+        &self.context.tile_grid
+    }
+
+    // Change the tile resolution; meant to be called before any reservation is made
+    pub fn set_tile_config(&mut self, config: TileConfig) {
+        self.context.tile_grid = TileGrid::new(&self.context.geometry, config);
+    }
+
+    pub fn conflict_matrix(&self) -> &ConflictMatrix {
+        &self.context.conflict_matrix
+    }
+
+    // Replace the movement pairs checked for conflicts, e.g. with overridden entries
+    pub fn set_conflict_matrix(&mut self, conflict_matrix: ConflictMatrix) {
+        self.context.conflict_matrix = conflict_matrix;
+    }
+
+    pub fn preemption_rule(&self) -> PreemptionRule {
+        self.context.preemption_rule
+    }
+
+    pub fn set_preemption_rule(&mut self, preemption_rule: PreemptionRule) {
+        self.context.preemption_rule = preemption_rule;
+    }
+
+    // Vehicles that lost their reservation to a preempting request since the last
+    // call; they have to stop before the box and ask again
+    pub fn take_revocations(&mut self) -> Vec<i32> {
+        self.policy.book_mut().take_revocations()
+    }
+
+    pub fn reservations(&self) -> &[Reservation] {
+        self.policy.book().reservations()
+    }
+
+    pub fn reservation(&self, vehicle_id: i32) -> Option<&Reservation> {
+        self.policy.query(vehicle_id)
+    }
+
+    pub fn reservation_counts(&self) -> ReservationCounts {
+        self.policy.book().counts()
+    }
+
+    // Let the policy catch up with the clock; called once per tick before any request
+    pub fn tick(&mut self, now: std::time::Duration) {
+        self.policy.tick(&self.context, now);
+    }
+
+    // Distance to the stop line from which vehicles ask for a reservation here
+    pub fn request_distance(&self) -> f32 {
+        self.policy.request_distance()
+    }
+
+    // Distance of the stop line to the box where vehicles without permission come to rest
+    pub fn stop_line(&self) -> Option<f32> {
+        self.policy.stop_line()
+    }
+
+    // The vehicle left the box; its reservation expires early. False when it held none.
+    pub fn release_reservation(&mut self, vehicle_id: i32) -> bool {
+        self.policy.release(vehicle_id)
+    }
+
+    // The vehicle gives up its reservation before crossing. False when it held none.
+    pub fn cancel_reservation(&mut self, vehicle_id: i32) -> bool {
+        self.policy.cancel(vehicle_id)
+    }
+
+    pub fn intersection_phase(&self, vehicle: &Vehicle) -> IntersectionPhase {
+        // Box bounds expressed in the vehicle's longitudinal coordinate
+        let (box_start, box_end) = self.context.geometry.intersection_extent(vehicle.movement_direction());
+        let front = vehicle.longitudinal_position() + vehicle.size / 2.0;
+        let rear = vehicle.longitudinal_position() - vehicle.size / 2.0;
+
+        if front < box_start {
+            IntersectionPhase::Approaching
+        } else if rear > box_end {
+            IntersectionPhase::Exited
+        } else {
+            IntersectionPhase::Inside
+        }
+    }
+
+    // Bumper-to-bumper distance from `vehicle` to `other` along the path of `vehicle`,
+    // negative when `other` is not in front of it
+    pub fn longitudinal_gap(vehicle: &Vehicle, other: &Vehicle) -> f32 {
+        let other_position = vehicle.longitudinal_coordinate(other.position);
+        other_position - vehicle.longitudinal_position() - (vehicle.size + other.size) / 2.0
+    }
+
+    // Two vehicles share a lane when they travel the same way and their lateral
+    // positions are closer than half a vehicle width
+    pub fn in_same_lane(vehicle: &Vehicle, other: &Vehicle) -> bool {
+        vehicle.movement_direction() == other.movement_direction()
+            && (vehicle.lateral_position() - other.lateral_position()).abs() < vehicle.size.max(other.size) / 2.0
+    }
+
+    // A vehicle turning towards `exit_direction` on a path ending at `end` joins the
+    // lane `other` drives in, and `other` is further along that lane than it
+    fn in_exit_lane(vehicle: &Vehicle, exit_direction: MovementDirection, end: Position, other: &Vehicle) -> bool {
+        let (dx, dy) = direction_vector(exit_direction);
+        let lateral = |position: Position| position.x * dy - position.y * dx;
+        let longitudinal = |position: Position| position.x * dx + position.y * dy;
+        other.turn_progress.is_none()
+            && other.movement_direction() == exit_direction
+            && (lateral(other.position) - lateral(end)).abs() < vehicle.size.max(other.size) / 2.0
+            && longitudinal(other.position) > longitudinal(vehicle.position)
+    }
+
+    // Bumper-to-bumper distance between the centers of two vehicles, the gap
+    // `PhysicsEngine::adjust_speed_for_safety` keeps
+    fn center_gap(vehicle: &Vehicle, other: &Vehicle) -> f32 {
+        let (dx, dy) = (other.position.x - vehicle.position.x, other.position.y - vehicle.position.y);
+        (dx * dx + dy * dy).sqrt() - (vehicle.size + other.size) / 2.0
+    }
+
+    // Closest vehicle in front of `current_vehicle` in the same lane. Vehicles at an
+    // earlier intersection phase are never leaders, whatever their coordinates say.
+    // A vehicle turning through the box also follows the vehicles in the lane it
+    // turns into, so it stops behind one standing just past the box.
     pub fn get_vehicle_ahead_in_same_direction(
+        &self,
         current_vehicle: &Vehicle,
         vehicles: &[Vehicle]
     ) -> Option<usize> {
-        vehicles
-            .iter()
-            .enumerate()
-            .filter(|&(_, v)| v.movement_direction == current_vehicle.movement_direction) // Same direction
-            .filter(|&(_, v)| v.distance_to_intersection < current_vehicle.distance_to_intersection) // is ahead
-            .min_by(|&(_, a), &(_, b)|
-                a.distance_to_intersection.partial_cmp(&b.distance_to_intersection).unwrap()
-            ) // closest vehicle ahead
-            .map(|(index, _)| index) // return only the index
+        self.get_vehicle_ahead_among(current_vehicle, vehicles, 0..vehicles.len())
     }
-    pub fn calculate_reservation_window(
+
+    // Same as `get_vehicle_ahead_in_same_direction`, restricted to the vehicle indices
+    // in `candidates`, typically the result of a spatial index query
+    pub fn get_vehicle_ahead_among(
         &self,
-        vehicle: &Vehicle
-    ) -> (std::time::Duration, std::time::Duration) {
-        let entry_time = std::time::Duration::from_secs_f32(vehicle.time_to_intersection);
-        let time_to_cross = vehicle.size / vehicle.velocity;
-        let exit_time = entry_time + std::time::Duration::from_secs_f32(time_to_cross);
-        (entry_time, exit_time)
-    }
-
-    pub fn request_reservation(&mut self, vehicle: &Vehicle) -> Result<(), &'static str> {
-        let (start_time, end_time) = self.calculate_reservation_window(vehicle);
-    
-        let mut to_remove = Vec::new(); // Step 1: Create a Vec to store indices
-    
-        for (index, existing_reservation) in self.reservations.iter().enumerate() {
-            if self.has_conflict(
-                start_time,
-                end_time,
-                vehicle.turn_direction,
-                vehicle.movement_direction,
-                vehicle.lane,
-                existing_reservation,
-            ) {
-                if start_time < existing_reservation.start_time {
-                    to_remove.push(index); // Step 2: Populate the Vec
+        current_vehicle: &Vehicle,
+        vehicles: &[Vehicle],
+        candidates: impl Iterator<Item = usize>
+    ) -> Option<usize> {
+        let current_phase = self.intersection_phase(current_vehicle);
+        let exit_lane = current_vehicle.turn_progress.as_ref().map(|progress| (progress.exit_direction, progress.path.end()));
+        candidates
+            .map(|index| (index, &vehicles[index]))
+            .filter(|&(_, v)| v.id != current_vehicle.id)
+            .filter(|&(_, v)| self.intersection_phase(v) >= current_phase)
+            .filter_map(|(index, v)| {
+                let gap = Self::longitudinal_gap(current_vehicle, v);
+                if Self::in_same_lane(current_vehicle, v) && gap > -(current_vehicle.size + v.size) / 2.0 {
+                    Some((index, gap)) // center is ahead
+                } else if exit_lane.is_some_and(|(direction, end)| Self::in_exit_lane(current_vehicle, direction, end, v)) {
+                    Some((index, Self::center_gap(current_vehicle, v)))
                 } else {
-                    return Err("Reservation conflict");
+                    None
                 }
-            }
-        }
-    
-        // Step 3: Remove the reservations at the collected indices
-        for index in to_remove.iter().rev() {
-            self.reservations.remove(*index);
-        }
-    
-        let reservation = Reservation {
-            vehicle_id: vehicle.id,
-            turn_direction: vehicle.turn_direction,
-            movement_direction: vehicle.movement_direction,
-            vehicle_lane: vehicle.lane,
-            start_time,
-            end_time,
-        };
-        self.reservations.push(reservation);
-        Ok(())
-    }
-    
-
-    pub fn has_conflict(
+            })
+            .min_by(|&(a_index, a), &(b_index, b)|
+                a.partial_cmp(&b).unwrap().then(a_index.cmp(&b_index))
+            ) // closest vehicle ahead, lowest index on ties
+            .map(|(index, _)| index) // return only the index
+    }
+    pub fn calculate_reservation_window(
         &self,
-        proposed_start: std::time::Duration,
-        proposed_end: std::time::Duration,
-        proposed_turn_direction: TurnDirection,
-        proposed_movement_direction: MovementDirection, 
-        proposed_lane: Lane,
-        existing_reservation: &Reservation
-    ) -> bool {
-         // Check time overlap
-    if proposed_start <= existing_reservation.end_time && proposed_end >= existing_reservation.start_time {
-        // Check for straight movement in opposite directions
-        if proposed_turn_direction == TurnDirection::Straight && existing_reservation.turn_direction == TurnDirection::Straight {
-            match (proposed_movement_direction, existing_reservation.movement_direction) {
-                (MovementDirection::Up, MovementDirection::Down) | 
-                (MovementDirection::Down, MovementDirection::Up) | 
-                (MovementDirection::Right, MovementDirection::Left) | 
-                (MovementDirection::Left, MovementDirection::Right) => {
-                    return false; // No conflict for opposite straight directions
-                }
-                _ => {}
-            }
-        }
-        
-        // Check for left turn conflicts
-        if proposed_turn_direction == TurnDirection::Left {
-            return true; // Left turn conflicts with everything in the intersection
-        }
-        
-        // Check for conflicts with left turning vehicles
-        if existing_reservation.turn_direction == TurnDirection::Left {
-            return true; // Anything in the intersection conflicts with a left turning vehicle
-        }
-        
-        // ... Add any other specific conflict rules here ...
+        vehicle: &Vehicle,
+        now: std::time::Duration
+    ) -> (std::time::Duration, std::time::Duration) {
+        self.context.calculate_reservation_window(vehicle, now)
+    }
 
-        return true; // Default to conflict for safety
+    pub fn request_reservation(&mut self, vehicle: &Vehicle, now: std::time::Duration) -> Result<(), &'static str> {
+        self.policy.request(&self.context, vehicle, now)
     }
-    false // No time overlap, so no conflict
-}
 }
-
-
@@ -0,0 +1,70 @@
+pub mod vehicle;
+pub mod cli;
+pub mod collision;
+pub mod conflict_matrix;
+pub mod demand;
+pub mod geometry;
+pub mod intersection_manager;
+pub mod network;
+pub mod path;
+pub mod physics_engine;
+pub mod policy;
+pub mod playback;
+pub mod recording;
+pub mod route;
+pub mod scenario;
+pub mod simulation;
+pub mod spatial_index;
+pub mod spawner;
+pub mod stats;
+pub mod tiles;
+pub mod trace;
+
+pub use simulation::Simulation;
+
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TurnDirection {
+    Left,
+    Straight,
+    Right,
+}
+
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MovementDirection {
+    Up,
+    Down,
+    Left,
+    Right,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Position {
+    pub x: f32,
+    pub y: f32,
+}
+
+impl Position {
+    pub fn new(x: f32, y: f32) -> Self {
+        Position { x, y }
+    }
+}
+
+use std::ops::Add;
+impl Add<f32> for Position {
+    type Output = Position;
+
+    fn add(self, other: f32) -> Position {
+        Position { x: self.x + other, y: self.y + other }
+    }
+}
+
+use std::ops::Sub;
+impl Sub for Position {
+    type Output = f32;
+
+    fn sub(self, other: Position) -> f32 {
+        (self.x - other.x).hypot(self.y - other.y)
+    }
+}
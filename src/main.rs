@@ -1,78 +1,209 @@
 extern crate sdl2;
 use sdl2::event::Event;
 use sdl2::keyboard::Keycode;
-use vehicle::*;
-use intersection_manager::IntersectionManager;
-
- mod vehicle;
- mod intersection_manager;
- mod physics_engine;
-
-#[derive(Debug, PartialEq, Eq, Clone, Copy)]
-pub enum TurnDirection {
-    Left,
-    Straight,
-    Right,
-}
+use smart_road::cli;
+use smart_road::geometry::Geometry;
+use smart_road::playback::TracePlayback;
+use smart_road::recording::{InputEvent, Recorder, Recording};
+use smart_road::spawner::APPROACHES;
+use smart_road::stats::Statistics;
+use smart_road::vehicle::{footprint, ReservationStatus};
+use smart_road::{MovementDirection, Position, Simulation};
+use std::time::{Duration, Instant};
+
+mod font;
 
-#[derive(Debug, PartialEq, Eq, Clone, Copy)]
-pub enum MovementDirection {
-    Up,
-    Down,
-    Left,
-    Right,
-}
+fn main() {
+    let args = cli::args();
+    if let Some(path) = exit_on_error(cli::arg_value(&args, "--view-trace")) {
+        view_trace(&args, &path);
+        return;
+    }
 
-#[derive(Debug, Clone, Copy)]
-pub struct Position {
-    x: f32,
-    y: f32,
-}
+    // A replay takes scenario, seed and input from the recording, ignoring the keyboard
+    let (scenario, seed, mut simulation, mut replayer) = match exit_on_error(cli::arg_value(&args, "--replay")) {
+        Some(path) => {
+            let recording = exit_on_error(Recording::load(&path).map_err(|e| format!("Could not load recording: {}", e)));
+            let scenario = exit_on_error(recording.scenario());
+            let (simulation, replayer) = exit_on_error(recording.start());
+            (scenario, recording.seed, simulation, Some(replayer))
+        }
+        None => {
+            let scenario = exit_on_error(cli::scenario_from_args(&args));
+            let seed = scenario.seed.unwrap_or_else(rand::random);
+            let simulation = exit_on_error(scenario.build(seed).map_err(|e| format!("Invalid scenario: {}", e)));
+            (scenario, seed, simulation, None)
+        }
+    };
+    println!("Seed: {}", seed);
+    simulation.set_tracer(exit_on_error(cli::tracer_from_args(&args).map_err(|e| format!("Could not start trace: {}", e))));
+    let record_path = exit_on_error(cli::arg_value(&args, "--record"));
+    let mut recorder = record_path.as_ref().map(|_| Recorder::new(&scenario, seed));
 
-impl Position {
-    pub fn new(x: f32, y: f32) -> Self {
-        Position { x, y }
-    }
-}
+    let sdl_context = sdl2::init().unwrap();
+    let geometries: Vec<Geometry> = simulation.network().nodes().iter().map(|node| node.geometry().clone()).collect();
+    let mut canvas = open_window(&sdl_context, &geometries[0]);
+    let mut event_pump = sdl_context.event_pump().unwrap();
 
-use std::ops::Add;
-impl Add<f32> for Position {
-    type Output = Position;
+    let mut last_frame = Instant::now();
+    let mut show_summary = false;
+    let mut paused = false;
+    // Ticks requested one at a time while paused
+    let mut single_steps = 0;
+
+    'running: loop {
+        for event in event_pump.poll_iter() {
+            match event {
+                Event::Quit { .. } => {
+                    break 'running;
+                }
+                // First Escape ends the run and shows the statistics, the second one quits
+                Event::KeyDown { keycode: Some(Keycode::Escape), .. } => {
+                    if show_summary {
+                        break 'running;
+                    }
+                    show_summary = true;
+                }
+                _ if show_summary => {}
+                // Controls for inspecting the run, also available during a replay
+                Event::KeyDown { keycode: Some(Keycode::Space), .. } => {
+                    paused = !paused;
+                }
+                Event::KeyDown { keycode: Some(Keycode::N), .. } if paused => {
+                    single_steps += 1;
+                }
+                Event::KeyDown { keycode: Some(Keycode::Equals | Keycode::KpPlus), .. } => {
+                    simulation.set_time_scale(simulation.time_scale() * 2.0);
+                }
+                Event::KeyDown { keycode: Some(Keycode::Minus | Keycode::KpMinus), .. } => {
+                    simulation.set_time_scale(simulation.time_scale() / 2.0);
+                }
+                _ if replayer.is_some() => {}
+                Event::KeyDown { keycode: Some(keycode), .. } => {
+                    if let Some(input) = input_event(keycode) {
+                        match recorder.as_mut() {
+                            Some(recorder) => recorder.apply(input, &mut simulation),
+                            None => input.apply(&mut simulation),
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        // A scenario with a duration, or a replay reaching the end of its recording, ends on its own
+        if scenario.duration.is_some_and(|duration| simulation.time() >= duration) {
+            show_summary = true;
+        }
+        if replayer.as_ref().is_some_and(|replayer| replayer.is_finished(&simulation)) {
+            show_summary = true;
+        }
+
+        if show_summary {
+            draw_summary(&mut canvas, simulation.statistics());
+            canvas.present();
+            std::thread::sleep(std::time::Duration::from_millis(16));
+            continue;
+        }
 
-    fn add(self, other: f32) -> Position {
-        Position { x: self.x + other, y: self.y + other }
+        // Run the fixed-timestep physics for the real time spent since the last
+        // frame, or only the ticks asked for one by one while paused
+        let now = Instant::now();
+        let frame_time = if paused { Duration::ZERO } else { now - last_frame };
+        let ticks = simulation.accumulate(frame_time) + std::mem::take(&mut single_steps);
+        for _ in 0..ticks {
+            match replayer.as_mut() {
+                Some(replayer) if replayer.is_finished(&simulation) => {}
+                Some(replayer) => replayer.step(&mut simulation),
+                None => simulation.step(),
+            }
+        }
+        last_frame = now;
+
+        draw_network(&mut canvas, &geometries);
+
+        // Draw vehicles
+        canvas.set_draw_color(sdl2::pixels::Color::RGB(255, 0, 0));
+        for vehicle in simulation.vehicles() {
+            fill_polygon(&mut canvas, &vehicle.corners()); // Vehicles are squares rotated by their heading
+        }
+        draw_status(&mut canvas, &simulation, paused);
+        canvas.present(); // Present the rendered frame
+
+        std::thread::sleep(std::time::Duration::from_millis(16)); // Delay for ~60 FPS
+    }
+
+    println!("{}", simulation.statistics().to_json());
+    if let Err(e) = simulation.finish_trace() {
+        eprintln!("Could not write trace: {}", e);
+    }
+    if let (Some(path), Some(recorder)) = (record_path, recorder) {
+        match recorder.finish(&simulation).save(&path) {
+            Ok(()) => println!("Recording written to {}", path),
+            Err(e) => eprintln!("Could not write recording: {}", e),
+        }
     }
 }
 
-use std::ops::Sub;
-use physics_engine::PhysicsEngine;
-impl Sub for Position {
-    type Output = f32;
+// Clock, speed and reservation states in the top left corner
+fn draw_status(canvas: &mut sdl2::render::Canvas<sdl2::video::Window>, simulation: &Simulation, paused: bool) {
+    let count = |status: ReservationStatus| simulation.vehicles().iter().filter(|v| v.reservation_status == status).count();
+    let reservations = simulation.reservation_counts();
+    let lines = [
+        format!(
+            "T {:.2} S  TICK {}  X{}{}",
+            simulation.time().as_secs_f32(),
+            simulation.ticks(),
+            simulation.time_scale(),
+            if paused { "  PAUSED" } else { "" }
+        ),
+        format!(
+            "VEHICLES {}  GRANTED {}  WAITING {}",
+            simulation.vehicles().len(),
+            count(ReservationStatus::Granted),
+            count(ReservationStatus::Waiting)
+        ),
+        format!(
+            "RESERVATIONS {}  EXPIRED {}  CANCELLED {}  REVOKED {}",
+            reservations.active, reservations.expired, reservations.cancelled, reservations.revoked
+        ),
+    ];
+
+    canvas.set_draw_color(sdl2::pixels::Color::RGB(255, 255, 255));
+    let mut y = 10;
+    for line in lines {
+        font::draw_text(canvas, &line, 10, y, 2);
+        y += font::line_height(2);
+    }
+}
 
-    fn sub(self, other: Position) -> f32 {
-        (self.x - other.x).hypot(self.y - other.y)
+// Input a key stands for while the simulation runs
+fn input_event(keycode: Keycode) -> Option<InputEvent> {
+    match keycode {
+        Keycode::Up => Some(InputEvent::SpawnRandomTurn { approach: MovementDirection::Up }),
+        Keycode::Down => Some(InputEvent::SpawnRandomTurn { approach: MovementDirection::Down }),
+        Keycode::Left => Some(InputEvent::SpawnRandomTurn { approach: MovementDirection::Left }),
+        Keycode::Right => Some(InputEvent::SpawnRandomTurn { approach: MovementDirection::Right }),
+        Keycode::R => Some(InputEvent::SpawnRandom),
+        // Toggle continuous traffic generation
+        Keycode::A => Some(InputEvent::ToggleAutoSpawn),
+        _ => None,
     }
 }
 
+// Play back the trajectories of a trace file on the network described by the
+// scenario flags. Space pauses, Left/Right seek by a second (ten with Shift),
+// Up/Down double or halve the speed and Home goes back to the start.
+fn view_trace(args: &[String], path: &str) {
+    let mut playback = exit_on_error(TracePlayback::load(path).map_err(|e| format!("Could not load trace: {}", e)));
+    let scenario = exit_on_error(cli::scenario_from_args(args));
+    let network = exit_on_error(scenario.network().map_err(|e| format!("Invalid scenario: {}", e)));
+    let geometries: Vec<Geometry> = network.nodes().iter().map(|node| node.geometry().clone()).collect();
 
-fn main() {
     let sdl_context = sdl2::init().unwrap();
-    let video_subsystem = sdl_context.video().unwrap();
-
-    let window = video_subsystem.window("Traffic Simulation", 1000, 1000)
-    .position_centered()
-    .build()
-    .unwrap();
-
-
-    let mut canvas = window.into_canvas().build().unwrap();
-
+    let mut canvas = open_window(&sdl_context, &geometries[0]);
     let mut event_pump = sdl_context.event_pump().unwrap();
-
-    let mut vehicles = Vec::new();
-    let mut im = IntersectionManager::new();
-    let physics_engine = PhysicsEngine::new(5.0, 50.0); // Safety distance of 5 units
-    let mut next_vehicle_id: i32 = 1;
+    let mut last_frame = Instant::now();
 
     'running: loop {
         for event in event_pump.poll_iter() {
@@ -80,209 +211,141 @@ fn main() {
                 Event::Quit { .. } | Event::KeyDown { keycode: Some(Keycode::Escape), .. } => {
                     break 'running;
                 }
-                Event::KeyDown { keycode: Some(Keycode::Up), .. } => {
-                    let turn_direction;
-                    let x = if rand::random::<f32>() < 0.33 {
-                        turn_direction = TurnDirection::Right;
-                        9.75 * 56.0
-                    } else if rand::random::<f32>() < 0.5 {
-                        turn_direction = TurnDirection::Straight;
-                        10.7 * 56.0
-                    } else {
-                        turn_direction = TurnDirection::Left;
-                        11.5 * 56.0
-                    };
-                    let vehicle = Vehicle {
-                        position: Position { x, y: 867.0 },
-                        size: 55.0,
-                        velocity: 10.0,
-                        movement_direction: MovementDirection::Up,
-                        turn_direction,
-                        distance_to_intersection: 0.0,
-                        time_to_intersection: 0.0,
-                        id: next_vehicle_id,
-                        acceleration: 0.0,
-                        lane: Lane::Left,
-                    };
-                    vehicles.push(vehicle);
-                    next_vehicle_id += 1;
-                }
-                
-                Event::KeyDown { keycode: Some(Keycode::Down), .. } => {
-                    let turn_direction = if rand::random::<f32>() < 0.33 {
-                        TurnDirection::Left
-                    } else if rand::random::<f32>() < 0.5 {
-                        TurnDirection::Straight
+                Event::KeyDown { keycode: Some(keycode), keymod, .. } => {
+                    let step = if keymod.intersects(sdl2::keyboard::Mod::LSHIFTMOD | sdl2::keyboard::Mod::RSHIFTMOD) {
+                        10.0
                     } else {
-                        TurnDirection::Right
-                    };
-                
-                    let x = match turn_direction {
-                        TurnDirection::Right => 6.6 * 56.0,
-                        TurnDirection::Straight => 7.6 * 56.0,
-                        TurnDirection::Left => 8.6 * 56.0,
-                    };
-                    let vehicle = Vehicle {
-                        position: Position { x, y: -67.0 },
-                        size: 55.0,
-                        velocity: 10.0,
-                        movement_direction: MovementDirection::Down,
-                        turn_direction,
-                        distance_to_intersection: 0.0,
-                        time_to_intersection: 0.0,
-                        id: next_vehicle_id,
-                        acceleration: 0.0,
-                        lane: Lane::Right,
+                        1.0
                     };
-                    vehicles.push(vehicle);
-                    next_vehicle_id += 1;
-                }
-                
-                Event::KeyDown { keycode: Some(Keycode::Left), .. } => {
-                    let turn_direction = if rand::random::<f32>() < 0.33 {
-                        TurnDirection::Left
-                    } else if rand::random::<f32>() < 0.5 {
-                        TurnDirection::Straight
-                    } else {
-                        TurnDirection::Right
-                    };
-                
-                    let y = match turn_direction {
-                        TurnDirection::Right => 6.6 * 56.0,
-                        TurnDirection::Straight => 7.6 * 56.0,
-                        TurnDirection::Left => 8.6 * 56.0,
-                    };
-                    let vehicle = Vehicle {
-                        position: Position { x: 867.0, y },
-                        size: 55.0,
-                        velocity: 10.0,
-                        movement_direction: MovementDirection::Left,
-                        turn_direction,
-                        distance_to_intersection: 0.0,
-                        time_to_intersection: 0.0,
-                        id: next_vehicle_id,
-                        acceleration: 0.0,
-                        lane: Lane::Left,
-                    };
-                    vehicles.push(vehicle);
-                    next_vehicle_id += 1;
-                }
-                
-                Event::KeyDown { keycode: Some(Keycode::Right), .. } => {
-                    let turn_direction = if rand::random::<f32>() < 0.33 {
-                        TurnDirection::Left
-                    } else if rand::random::<f32>() < 0.5 {
-                        TurnDirection::Straight
-                    } else {
-                        TurnDirection::Right
-                    };
-                    
-                    let y = match turn_direction {
-                        TurnDirection::Left => 9.5 * 56.0,
-                        TurnDirection::Straight => 10.5 * 56.0,
-                        TurnDirection::Right => 11.2 * 56.0,
-                    };
-                    let vehicle = Vehicle {
-                        position: Position { x: -67.0, y },
-                        size: 55.0,
-                        velocity: 10.0,
-                        lane: Lane::Right,
-                        movement_direction: MovementDirection::Right,
-                        turn_direction,
-                        distance_to_intersection: 0.0,
-                        time_to_intersection: 0.0,
-                        id: next_vehicle_id,
-                        acceleration: 0.0,
-                        
-                    };
-                    vehicles.push(vehicle);
-                    next_vehicle_id += 1;
+                    match keycode {
+                        Keycode::Space => playback.set_paused(!playback.is_paused()),
+                        Keycode::Left => playback.seek_by(-step),
+                        Keycode::Right => playback.seek_by(step),
+                        Keycode::Up => playback.set_speed(playback.speed() * 2.0),
+                        Keycode::Down => playback.set_speed(playback.speed() / 2.0),
+                        Keycode::Home => playback.seek(playback.start_time()),
+                        _ => {}
+                    }
                 }
-                
                 _ => {}
             }
         }
 
-        let mut adjustments: Vec<(usize, f32)> = Vec::new(); // Stores (index, new_speed)
-        let mut vehicle_pairs: Vec<(usize, usize)> = Vec::new();
+        let now = Instant::now();
+        playback.advance(now - last_frame);
+        last_frame = now;
 
-        for i in 0..vehicles.len() {
-            physics_engine.update(&mut vehicles[i], 1.0);
-            vehicles[i].update_distance_and_time_to_intersection();
-            println!("Vehicle {} distance to intersection: {}", vehicles[i].id, vehicles[i].distance_to_intersection);
+        draw_network(&mut canvas, &geometries);
+        canvas.set_draw_color(sdl2::pixels::Color::RGB(255, 0, 0));
+        for vehicle in &playback.current_frame().vehicles {
+            fill_polygon(&mut canvas, &footprint(vehicle.position, vehicle.heading, vehicle.size));
+        }
 
+        let status = format!(
+            "T {:.2} / {:.2} S  X{}{}",
+            playback.time().as_secs_f32(),
+            playback.end_time().as_secs_f32(),
+            playback.speed(),
+            if playback.is_paused() { "  PAUSED" } else { "" }
+        );
+        canvas.set_draw_color(sdl2::pixels::Color::RGB(255, 255, 255));
+        font::draw_text(&mut canvas, &status, 10, 10, 2);
+        canvas.present();
+
+        std::thread::sleep(std::time::Duration::from_millis(16));
+    }
+}
 
-                // Check if vehicle is at the intersection
-                if vehicles[i].distance_to_intersection < 5.0 && vehicles[i].distance_to_intersection > 0.0 {
-                    println!("Vehicle {} reached intersection with turn direction: {:?}", vehicles[i].id, vehicles[i].turn_direction);
-                    vehicles[i].update_direction_at_intersection();
-                    println!("Vehicle {} new movement direction: {:?}", vehicles[i].id, vehicles[i].movement_direction);
-                }
-                
+// Print the error and quit, for setup failures before the window opens
+fn exit_on_error<T>(result: Result<T, String>) -> T {
+    result.unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    })
+}
 
-                if let Some(vehicle_ahead_index) = IntersectionManager::get_vehicle_ahead_in_same_direction(&vehicles[i], &vehicles) {
-                    vehicle_pairs.push((i, vehicle_ahead_index));
-                }
-                
-
-            if
-                vehicles[i].distance_to_intersection < 20.0 &&
-                vehicles[i].time_to_intersection > 0.0
-            {
-                match im.request_reservation(&vehicles[i]) {
-                    Ok(_) => {
-                        println!("Reservation granted for vehicle {}", vehicles[i].id);
-                    }
-                    Err(e) => {
-                        println!("Reservation error for vehicle {}: {}", vehicles[i].id, e);
-                        vehicles[i].velocity = 0.0;
-                    }
-                }
-            }
-        }
+// Window sized to the world of `geometry`
+fn open_window(sdl_context: &sdl2::Sdl, geometry: &Geometry) -> sdl2::render::Canvas<sdl2::video::Window> {
+    let video_subsystem = sdl_context.video().unwrap();
+    let window = video_subsystem.window("Traffic Simulation", geometry.world_width as u32, geometry.world_height as u32)
+    .position_centered()
+    .build()
+    .unwrap();
 
-        for (vehicle_index, vehicle_ahead_index) in vehicle_pairs {
-            let new_speed = physics_engine.adjust_speed_for_safety(
-                &vehicles[vehicle_index],
-                &vehicles[vehicle_ahead_index]
-            );
-            adjustments.push((vehicle_index, new_speed));
-        }
+    window.into_canvas().build().unwrap()
+}
 
-        for (index, new_speed) in adjustments {
-            vehicles[index].velocity = new_speed;
-        }
+// Background and roads of every intersection
+fn draw_network(canvas: &mut sdl2::render::Canvas<sdl2::video::Window>, geometries: &[Geometry]) {
+    // Clear canvas with a green background.
+    canvas.set_draw_color(sdl2::pixels::Color::RGB(0, 128, 0)); // Green color
+    canvas.clear();
+
+    // draw_grid(&mut canvas);
+    // Every layer is drawn for all intersections before the next one, so the
+    // boxes cover the lines of the roads crossing them
+    for geometry in geometries {
+        draw_roads(canvas, geometry, 0);
+    }
+    for geometry in geometries {
+        draw_boundary_lines(canvas, geometry);
+    }
+    // Repaint the road surfaces inside their edges, removing boundary lines
+    // of one road where they cross another
+    for geometry in geometries {
+        draw_roads(canvas, geometry, BOUNDARY_THICKNESS);
+    }
+    for geometry in geometries {
+        draw_center_lines(canvas, geometry);
+    }
+    for geometry in geometries {
+        draw_intersection(canvas, geometry);
+        draw_stop_lines(canvas, geometry);
+    }
+}
 
-        vehicles.retain(|vehicle| {
-            match vehicle.movement_direction {
-                MovementDirection::Up => vehicle.position.y >= 0.0,
-                MovementDirection::Down => vehicle.position.y <= 1000.0,
-                MovementDirection::Left => vehicle.position.x >= 0.0,
-                MovementDirection::Right => vehicle.position.x <= 1000.0,
+// Scanline fill of a convex polygon
+fn fill_polygon(canvas: &mut sdl2::render::Canvas<sdl2::video::Window>, corners: &[Position]) {
+    let min_y = corners.iter().map(|c| c.y).fold(f32::MAX, f32::min).round() as i32;
+    let max_y = corners.iter().map(|c| c.y).fold(f32::MIN, f32::max).round() as i32;
+
+    for y in min_y..=max_y {
+        let scan_y = y as f32 + 0.5;
+        let mut min_x = f32::MAX;
+        let mut max_x = f32::MIN;
+        for (i, from) in corners.iter().enumerate() {
+            let to = corners[(i + 1) % corners.len()];
+            if (from.y <= scan_y && to.y > scan_y) || (to.y <= scan_y && from.y > scan_y) {
+                let x = from.x + (scan_y - from.y) / (to.y - from.y) * (to.x - from.x);
+                min_x = min_x.min(x);
+                max_x = max_x.max(x);
             }
-        });
-
-           // Clear canvas with a green background.
-            canvas.set_draw_color(sdl2::pixels::Color::RGB(0, 128, 0)); // Green color
-            canvas.clear();
+        }
+        if min_x <= max_x {
+            canvas.draw_line((min_x.round() as i32, y), (max_x.round() as i32, y)).unwrap();
+        }
+    }
+}
 
-       // draw_grid(&mut canvas);
-        draw_roads(&mut canvas);
-        draw_boundary_lines(&mut canvas);
-        draw_center_lines(&mut canvas);
-        draw_intersection(&mut canvas);
+fn draw_summary(canvas: &mut sdl2::render::Canvas<sdl2::video::Window>, statistics: &Statistics) {
+    canvas.set_draw_color(sdl2::pixels::Color::RGB(20, 20, 20));
+    canvas.clear();
 
-        // Draw vehicles
-        canvas.set_draw_color(sdl2::pixels::Color::RGB(255, 0, 0));
-        for vehicle in &vehicles {
-            let x = (vehicle.position.x as i32) - (vehicle.size as i32 / 2);
-            let y = (vehicle.position.y as i32) - (vehicle.size as i32 / 2);
-            canvas.fill_rect(sdl2::rect::Rect::new(x, y, vehicle.size as u32,vehicle.size as u32)).unwrap(); // Vehicles are 10x10 squares
-        }
-        canvas.present(); // Present the rendered frame
+    let scale = 4;
+    let mut y = 200;
+    canvas.set_draw_color(sdl2::pixels::Color::RGB(255, 255, 0));
+    font::draw_text(canvas, "Statistics", 150, y, scale);
+    y += 2 * font::line_height(scale);
 
-        std::thread::sleep(std::time::Duration::from_millis(16)); // Delay for ~60 FPS
+    canvas.set_draw_color(sdl2::pixels::Color::RGB(255, 255, 255));
+    for line in statistics.summary_lines() {
+        font::draw_text(canvas, &line, 150, y, scale);
+        y += font::line_height(scale);
     }
+
+    y += font::line_height(scale);
+    canvas.set_draw_color(sdl2::pixels::Color::RGB(150, 150, 150));
+    font::draw_text(canvas, "Press Escape to quit", 150, y, 3);
 }
 
 /* 
@@ -298,88 +361,93 @@ fn draw_grid(canvas: &mut sdl2::render::Canvas<sdl2::video::Window>) {
     }
 }
  */
-fn draw_roads(canvas: &mut sdl2::render::Canvas<sdl2::video::Window>) {
+// Thickness of the black road edges
+const BOUNDARY_THICKNESS: i32 = 4;
+
+// Both roads through an intersection, shrunk by `inset` on each side
+fn draw_roads(canvas: &mut sdl2::render::Canvas<sdl2::video::Window>, geometry: &Geometry, inset: i32) {
     canvas.set_draw_color(sdl2::pixels::Color::RGB(100, 100, 100)); // Gray color for roads
-    
+    let (min, max) = geometry.intersection_bounds();
+    let road_width = ((max.x - min.x).round() as i32 - 2 * inset) as u32;
+
     // Vertical roads
-    canvas.fill_rect(sdl2::rect::Rect::new(6 * 56, 0, 6 * 56, 1000)).unwrap();
-    
+    canvas.fill_rect(sdl2::rect::Rect::new(min.x.round() as i32 + inset, 0, road_width, geometry.world_height as u32)).unwrap();
+
     // Horizontal roads
-    canvas.fill_rect(sdl2::rect::Rect::new(0, 6 * 56, 1000, 6 * 56)).unwrap();
+    canvas.fill_rect(sdl2::rect::Rect::new(0, min.y.round() as i32 + inset, geometry.world_width as u32, road_width)).unwrap();
 }
 
-fn draw_intersection(canvas: &mut sdl2::render::Canvas<sdl2::video::Window>) {
+fn draw_intersection(canvas: &mut sdl2::render::Canvas<sdl2::video::Window>, geometry: &Geometry) {
     canvas.set_draw_color(sdl2::pixels::Color::RGB(100, 100, 100)); // Gray color for roads
-    canvas.fill_rect(sdl2::rect::Rect::new(6 * 56, 6 * 56, 6 * 56, 6 * 56)).unwrap();
+    let (min, max) = geometry.intersection_bounds();
+    let size = (max.x - min.x).round() as u32;
+    canvas.fill_rect(sdl2::rect::Rect::new(min.x.round() as i32, min.y.round() as i32, size, size)).unwrap();
 }
 
 
-fn draw_center_lines(canvas: &mut sdl2::render::Canvas<sdl2::video::Window>) {
+fn draw_center_lines(canvas: &mut sdl2::render::Canvas<sdl2::video::Window>, geometry: &Geometry) {
     canvas.set_draw_color(sdl2::pixels::Color::RGB(255, 255, 0)); // Yellow color for center lines
 
-    let dash_length = 28; // half of the cell size
-    let space_length = 28; // another half of the cell size
-    let line_thickness = 4; // chosen thickness for the yellow center lines
-
-    let mut start_y = 0;
-    while start_y < 1000 {
-        // Vertical center lines
-        canvas.fill_rect(sdl2::rect::Rect::new((9 * 56 - line_thickness) as i32, start_y as i32, line_thickness as u32, dash_length)).unwrap();
-        canvas.fill_rect(sdl2::rect::Rect::new((9 * 56 + line_thickness) as i32, start_y as i32, line_thickness as u32, dash_length)).unwrap();
-                
-        // Horizontal center lines
-        canvas.fill_rect(sdl2::rect::Rect::new(start_y as i32, (9 * 56 - line_thickness) as i32, dash_length, line_thickness as u32)).unwrap();
-        canvas.fill_rect(sdl2::rect::Rect::new(start_y as i32, (9 * 56 + line_thickness) as i32, dash_length, line_thickness as u32)).unwrap();
-        
-        
+    let dash_length = (geometry.lane_width / 2.0).round() as i32; // half of the lane width
+    let space_length = dash_length;
+    let line_thickness: i32 = 4; // chosen thickness for the yellow center lines
+    let center_x = geometry.center.x.round() as i32;
+    let center_y = geometry.center.y.round() as i32;
+
+    // Vertical center lines
+    let mut start = 0;
+    while start < geometry.world_height as i32 {
+        canvas.fill_rect(sdl2::rect::Rect::new(center_x - line_thickness, start, line_thickness as u32, dash_length as u32)).unwrap();
+        canvas.fill_rect(sdl2::rect::Rect::new(center_x + line_thickness, start, line_thickness as u32, dash_length as u32)).unwrap();
+        start += dash_length + space_length;
+    }
 
-        start_y += dash_length + space_length;
+    // Horizontal center lines
+    start = 0;
+    while start < geometry.world_width as i32 {
+        canvas.fill_rect(sdl2::rect::Rect::new(start, center_y - line_thickness, dash_length as u32, line_thickness as u32)).unwrap();
+        canvas.fill_rect(sdl2::rect::Rect::new(start, center_y + line_thickness, dash_length as u32, line_thickness as u32)).unwrap();
+        start += dash_length + space_length;
     }
 }
 
-fn draw_boundary_lines(canvas: &mut sdl2::render::Canvas<sdl2::video::Window>) {
+fn draw_boundary_lines(canvas: &mut sdl2::render::Canvas<sdl2::video::Window>, geometry: &Geometry) {
     canvas.set_draw_color(sdl2::pixels::Color::RGB(0, 0, 0)); // Black color for boundary lines
 
-    let thickness: i32 = 4; // Adjusted thickness of boundary lines
+    let thickness = BOUNDARY_THICKNESS;
+    let (min, max) = geometry.intersection_bounds();
+    let (min_x, min_y) = (min.x.round() as i32, min.y.round() as i32);
+    let (max_x, max_y) = (max.x.round() as i32, max.y.round() as i32);
+    let world_width = geometry.world_width as i32;
+    let world_height = geometry.world_height as i32;
 
     // Vertical boundaries
-    canvas.fill_rect(sdl2::rect::Rect::new(6 * 56, 0, thickness as u32, 1000)).unwrap(); // Left boundary
-    canvas.fill_rect(sdl2::rect::Rect::new(12 * 56 - thickness, 0, thickness as u32, 1000)).unwrap(); // Right boundary
+    canvas.fill_rect(sdl2::rect::Rect::new(min_x, 0, thickness as u32, world_height as u32)).unwrap(); // Left boundary
+    canvas.fill_rect(sdl2::rect::Rect::new(max_x - thickness, 0, thickness as u32, world_height as u32)).unwrap(); // Right boundary
 
     // Horizontal boundaries - Upper
-    canvas.fill_rect(sdl2::rect::Rect::new(0, 6 * 56 - thickness, 6 * 56, thickness as u32)).unwrap(); // Left part
-    canvas.fill_rect(sdl2::rect::Rect::new(12 * 56, 6 * 56 - thickness, 1000 - 12 * 56, thickness as u32)).unwrap(); // Right part
+    canvas.fill_rect(sdl2::rect::Rect::new(0, min_y - thickness, min_x as u32, thickness as u32)).unwrap(); // Left part
+    canvas.fill_rect(sdl2::rect::Rect::new(max_x, min_y - thickness, (world_width - max_x) as u32, thickness as u32)).unwrap(); // Right part
 
     // Horizontal boundaries - Lower
-    canvas.fill_rect(sdl2::rect::Rect::new(0, 12 * 56, 6 * 56, thickness as u32)).unwrap(); // Left part
-    canvas.fill_rect(sdl2::rect::Rect::new(12 * 56, 12 * 56, 1000 - 12 * 56, thickness as u32)).unwrap(); // Right part
-
-    // Dashed lines for non-turning sections on intersection
-    let dash_length: i32 = 28; // half of the cell size
-    let space_length: i32 = 28; // another half of the cell size
-    let line_thickness: i32 = 4; // chosen thickness for the dashed lines
-
-    canvas.set_draw_color(sdl2::pixels::Color::RGB(255, 255, 255)); // White color for dashed lines
-    
-    let mut start_pos = 6 * 56;
-    while start_pos < 12 * 56 {
-        // Top line
-        canvas.fill_rect(sdl2::rect::Rect::new(start_pos, 6 * 56 - line_thickness, dash_length as u32, line_thickness as u32)).unwrap();
-        
-        // Bottom line
-        canvas.fill_rect(sdl2::rect::Rect::new(start_pos, 12 * 56, dash_length as u32, line_thickness as u32)).unwrap();
-
-        start_pos += dash_length + space_length;
-    }
-
-    start_pos = 6 * 56;
-    while start_pos < 12 * 56 {
-        // Left line
-        canvas.fill_rect(sdl2::rect::Rect::new(6 * 56 - line_thickness, start_pos, line_thickness as u32, dash_length as u32)).unwrap();
+    canvas.fill_rect(sdl2::rect::Rect::new(0, max_y, min_x as u32, thickness as u32)).unwrap(); // Left part
+    canvas.fill_rect(sdl2::rect::Rect::new(max_x, max_y, (world_width - max_x) as u32, thickness as u32)).unwrap(); // Right part
+}
 
-        // Right line
-        canvas.fill_rect(sdl2::rect::Rect::new(12 * 56, start_pos, line_thickness as u32, dash_length as u32)).unwrap();
-        
-        start_pos += dash_length + space_length;
+// Solid white line across the incoming lanes of every approach
+fn draw_stop_lines(canvas: &mut sdl2::render::Canvas<sdl2::video::Window>, geometry: &Geometry) {
+    canvas.set_draw_color(sdl2::pixels::Color::RGB(255, 255, 255));
+
+    let thickness = 4.0;
+    for movement_direction in APPROACHES {
+        let (from, to) = geometry.stop_line(movement_direction);
+        let (left, right) = (from.x.min(to.x), from.x.max(to.x));
+        let (top, bottom) = (from.y.min(to.y), from.y.max(to.y));
+        // Stop lines are axis-aligned, give the zero-length side its thickness
+        let width = (right - left).max(thickness);
+        let height = (bottom - top).max(thickness);
+        let x = if right - left < thickness { left - thickness / 2.0 } else { left };
+        let y = if bottom - top < thickness { top - thickness / 2.0 } else { top };
+        canvas.fill_rect(sdl2::rect::Rect::new(x.round() as i32, y.round() as i32, width.round() as u32, height.round() as u32)).unwrap();
     }
 }
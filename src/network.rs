@@ -0,0 +1,310 @@
+use crate::geometry::Geometry;
+use crate::intersection_manager::IntersectionManager;
+use crate::path::exit_direction;
+use crate::route::Route;
+use crate::spawner::{APPROACHES, TURNS};
+use crate::MovementDirection;
+use crate::Position;
+use crate::TurnDirection;
+use std::collections::{HashMap, VecDeque};
+
+// Distance between neighbouring intersection centers used by the frontend's grid layout
+pub const DEFAULT_GRID_SPACING: f32 = 600.0;
+
+// An intersection of the network, controlled by its own manager
+pub struct Node {
+    row: usize,
+    col: usize,
+    manager: IntersectionManager,
+    // Link leaving the intersection in each direction
+    outgoing: HashMap<MovementDirection, usize>,
+}
+
+impl Node {
+    pub fn row(&self) -> usize {
+        self.row
+    }
+
+    pub fn col(&self) -> usize {
+        self.col
+    }
+
+    pub fn manager(&self) -> &IntersectionManager {
+        &self.manager
+    }
+
+    pub fn geometry(&self) -> &Geometry {
+        self.manager.geometry()
+    }
+}
+
+// Directed road between two intersections, or between an intersection and the world edge.
+// Every link touches at least one node.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Link {
+    // None when the link starts at the world edge
+    pub from: Option<usize>,
+    // None when the link leaves the world
+    pub to: Option<usize>,
+    pub direction: MovementDirection,
+    pub lanes: usize,
+}
+
+// Rectangular grid of intersections joined by two-way roads. Vehicles travel along
+// links and are handed over to the manager of the next node when they leave one.
+pub struct RoadNetwork {
+    nodes: Vec<Node>,
+    links: Vec<Link>,
+}
+
+impl RoadNetwork {
+    // A single intersection laid out by `geometry`
+    pub fn single(geometry: Geometry) -> Self {
+        Self::build(geometry, 1, 1, 0.0)
+    }
+
+    // `rows` x `cols` intersections `spacing` apart. `template` describes the
+    // top-left intersection; the world grows to make room for the others.
+    pub fn grid(template: Geometry, rows: usize, cols: usize, spacing: f32) -> Result<Self, String> {
+        if rows == 0 || cols == 0 {
+            return Err(format!("grid needs at least one row and column, got {}x{}", rows, cols));
+        }
+        template.validate()?;
+        if (rows > 1 || cols > 1) && spacing <= 2.0 * template.road_half_width() {
+            return Err(format!(
+                "grid spacing {} must exceed the intersection width {}",
+                spacing,
+                2.0 * template.road_half_width()
+            ));
+        }
+        Ok(Self::build(template, rows, cols, spacing))
+    }
+
+    fn build(template: Geometry, rows: usize, cols: usize, spacing: f32) -> Self {
+        let world_width = template.world_width + spacing * (cols - 1) as f32;
+        let world_height = template.world_height + spacing * (rows - 1) as f32;
+
+        let mut nodes = Vec::with_capacity(rows * cols);
+        for row in 0..rows {
+            for col in 0..cols {
+                let geometry = Geometry {
+                    center: Position::new(
+                        template.center.x + spacing * col as f32,
+                        template.center.y + spacing * row as f32,
+                    ),
+                    world_width,
+                    world_height,
+                    ..template.clone()
+                };
+                nodes.push(Node {
+                    row,
+                    col,
+                    manager: IntersectionManager::with_geometry(geometry),
+                    outgoing: HashMap::new(),
+                });
+            }
+        }
+
+        let neighbour = |index: usize, direction: MovementDirection| -> Option<usize> {
+            let (row, col) = (index / cols, index % cols);
+            match direction {
+                MovementDirection::Up => row.checked_sub(1).map(|row| row * cols + col),
+                MovementDirection::Down => (row + 1 < rows).then(|| (row + 1) * cols + col),
+                MovementDirection::Left => col.checked_sub(1).map(|col| row * cols + col),
+                MovementDirection::Right => (col + 1 < cols).then(|| row * cols + col + 1),
+            }
+        };
+
+        let lanes = template.lanes_per_approach;
+        let mut links = Vec::new();
+        for (index, node) in nodes.iter_mut().enumerate() {
+            for direction in APPROACHES {
+                node.outgoing.insert(direction, links.len());
+                links.push(Link { from: Some(index), to: neighbour(index, direction), direction, lanes });
+            }
+        }
+        // Entry links feed the boundary intersections from the world edge
+        for direction in APPROACHES {
+            for index in 0..nodes.len() {
+                let opposite = match direction {
+                    MovementDirection::Up => MovementDirection::Down,
+                    MovementDirection::Down => MovementDirection::Up,
+                    MovementDirection::Left => MovementDirection::Right,
+                    MovementDirection::Right => MovementDirection::Left,
+                };
+                if neighbour(index, opposite).is_none() {
+                    links.push(Link { from: None, to: Some(index), direction, lanes });
+                }
+            }
+        }
+
+        RoadNetwork { nodes, links }
+    }
+
+    pub fn nodes(&self) -> &[Node] {
+        &self.nodes
+    }
+
+    pub fn node(&self, node: usize) -> &Node {
+        &self.nodes[node]
+    }
+
+    pub fn manager(&self, node: usize) -> &IntersectionManager {
+        &self.nodes[node].manager
+    }
+
+    pub fn manager_mut(&mut self, node: usize) -> &mut IntersectionManager {
+        &mut self.nodes[node].manager
+    }
+
+    pub fn links(&self) -> &[Link] {
+        &self.links
+    }
+
+    pub fn link(&self, link: usize) -> &Link {
+        &self.links[link]
+    }
+
+    // Link a vehicle takes when it leaves `node` travelling in `direction`
+    pub fn outgoing_link(&self, node: usize, direction: MovementDirection) -> usize {
+        self.nodes[node].outgoing[&direction]
+    }
+
+    // Links where vehicles enter the world, in a fixed order
+    pub fn entry_links(&self) -> impl Iterator<Item = usize> + '_ {
+        self.links.iter().enumerate().filter(|(_, link)| link.from.is_none()).map(|(index, _)| index)
+    }
+
+    // Links where vehicles leave the world, in a fixed order
+    pub fn exit_links(&self) -> impl Iterator<Item = usize> + '_ {
+        self.links.iter().enumerate().filter(|(_, link)| link.to.is_none()).map(|(index, _)| index)
+    }
+
+    // Entry links carrying traffic in `direction`
+    pub fn entry_links_towards(&self, direction: MovementDirection) -> Vec<usize> {
+        self.entry_links().filter(|&link| self.links[link].direction == direction).collect()
+    }
+
+    // Node whose manager is responsible for vehicles on `link`: the one it leads
+    // to, or the one it leaves for links running out of the world
+    pub fn reference_node(&self, link: usize) -> usize {
+        let link = &self.links[link];
+        link.to.or(link.from).expect("every link touches a node")
+    }
+
+    pub fn link_geometry(&self, link: usize) -> &Geometry {
+        self.nodes[self.reference_node(link)].geometry()
+    }
+
+    // Link a vehicle on `link` continues on after turning `turn_direction` at the
+    // node ahead, None when the link leaves the world
+    pub fn next_link(&self, link: usize, turn_direction: TurnDirection) -> Option<usize> {
+        let link = &self.links[link];
+        let node = link.to?;
+        Some(self.outgoing_link(node, exit_direction(link.direction, turn_direction)))
+    }
+
+    // Route with the fewest intersections from `from` to the exit link `to`, turns
+    // tried in a fixed order so ties always resolve the same way
+    pub fn shortest_route(&self, from: usize, to: usize) -> Option<Route> {
+        let mut previous: HashMap<usize, (usize, TurnDirection)> = HashMap::new();
+        let mut queue = VecDeque::from([from]);
+        while let Some(link) = queue.pop_front() {
+            if link == to {
+                let mut turns = Vec::new();
+                let mut current = to;
+                while current != from {
+                    let (before, turn_direction) = previous[&current];
+                    turns.push(turn_direction);
+                    current = before;
+                }
+                turns.reverse();
+                return (!turns.is_empty()).then(|| Route::new(turns));
+            }
+            for turn_direction in TURNS {
+                if let Some(next) = self.next_link(link, turn_direction) {
+                    if next != from && !previous.contains_key(&next) {
+                        previous.insert(next, (link, turn_direction));
+                        queue.push_back(next);
+                    }
+                }
+            }
+        }
+        None
+    }
+
+    // Follow `route` from `link` and return the exit link it ends on, or why it does not fit
+    pub fn validate_route(&self, link: usize, route: &Route) -> Result<usize, String> {
+        let mut current = link;
+        for (index, &turn_direction) in route.turns().iter().enumerate() {
+            current = self
+                .next_link(current, turn_direction)
+                .ok_or_else(|| format!("route has {} turns but leaves the world after {}", route.turns().len(), index))?;
+        }
+        if self.links[current].to.is_some() {
+            return Err(format!("route ends after {} turns inside the network", route.turns().len()));
+        }
+        Ok(current)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 2x2 grid and the entry link feeding its top-left intersection from the west
+    fn grid() -> (RoadNetwork, usize) {
+        let network = RoadNetwork::grid(Geometry::default(), 2, 2, DEFAULT_GRID_SPACING).unwrap();
+        let entry = network.entry_links_towards(MovementDirection::Right)[0];
+        assert_eq!(network.link(entry).to, Some(0));
+        (network, entry)
+    }
+
+    #[test]
+    fn shortest_route_takes_the_fewest_intersections() {
+        let (network, entry) = grid();
+
+        let east_exit = network.outgoing_link(1, MovementDirection::Right);
+        let route = network.shortest_route(entry, east_exit).unwrap();
+        assert_eq!(route.turns(), [TurnDirection::Straight, TurnDirection::Straight]);
+
+        let left_exit = network.outgoing_link(0, exit_direction(MovementDirection::Right, TurnDirection::Left));
+        let route = network.shortest_route(entry, left_exit).unwrap();
+        assert_eq!(route.turns(), [TurnDirection::Left]);
+
+        let far_exit = network.outgoing_link(3, MovementDirection::Down);
+        assert_eq!(network.shortest_route(entry, far_exit).unwrap().turns().len(), 3);
+    }
+
+    #[test]
+    fn shortest_route_needs_a_turn() {
+        let (network, entry) = grid();
+        assert_eq!(network.shortest_route(entry, entry), None);
+    }
+
+    #[test]
+    fn shortest_routes_validate() {
+        let (network, entry) = grid();
+        for exit in network.exit_links() {
+            let route = network.shortest_route(entry, exit).unwrap();
+            assert_eq!(network.validate_route(entry, &route), Ok(exit));
+        }
+    }
+
+    #[test]
+    fn validate_route_rejects_routes_of_the_wrong_length() {
+        let (network, entry) = grid();
+
+        let short = Route::single(TurnDirection::Straight);
+        assert_eq!(
+            network.validate_route(entry, &short),
+            Err("route ends after 1 turns inside the network".to_string())
+        );
+
+        let long = Route::new(vec![TurnDirection::Left, TurnDirection::Straight]);
+        assert_eq!(
+            network.validate_route(entry, &long),
+            Err("route has 2 turns but leaves the world after 1".to_string())
+        );
+    }
+}
@@ -0,0 +1,155 @@
+use crate::geometry::Geometry;
+use crate::MovementDirection;
+use crate::Position;
+use crate::TurnDirection;
+
+// Number of straight segments used to approximate a curved turning path
+const CURVE_SEGMENTS: usize = 32;
+// Control point distance, as a fraction of the turn radius, for which a cubic
+// Bézier closely matches a quarter circle
+const QUARTER_CIRCLE_HANDLE: f32 = 0.552_284_8;
+
+// A path sampled into a polyline, so vehicles can follow it by arc length
+#[derive(Debug, Clone)]
+pub struct Path {
+    points: Vec<Position>,
+    // Arc length from the first point to each point
+    cumulative: Vec<f32>,
+}
+
+impl Path {
+    pub fn line(from: Position, to: Position) -> Self {
+        Path::from_points(vec![from, to])
+    }
+
+    pub fn cubic_bezier(p0: Position, p1: Position, p2: Position, p3: Position) -> Self {
+        let points = (0..=CURVE_SEGMENTS)
+            .map(|i| {
+                let t = i as f32 / CURVE_SEGMENTS as f32;
+                let u = 1.0 - t;
+                let (a, b, c, d) = (u * u * u, 3.0 * u * u * t, 3.0 * u * t * t, t * t * t);
+                Position::new(
+                    a * p0.x + b * p1.x + c * p2.x + d * p3.x,
+                    a * p0.y + b * p1.y + c * p2.y + d * p3.y,
+                )
+            })
+            .collect();
+        Path::from_points(points)
+    }
+
+    fn from_points(points: Vec<Position>) -> Self {
+        let mut cumulative = Vec::with_capacity(points.len());
+        let mut length = 0.0;
+        cumulative.push(0.0);
+        for pair in points.windows(2) {
+            length += (pair[1].x - pair[0].x).hypot(pair[1].y - pair[0].y);
+            cumulative.push(length);
+        }
+        Path { points, cumulative }
+    }
+
+    pub fn length(&self) -> f32 {
+        *self.cumulative.last().unwrap()
+    }
+
+    pub fn start(&self) -> Position {
+        self.points[0]
+    }
+
+    pub fn end(&self) -> Position {
+        *self.points.last().unwrap()
+    }
+
+    // Position and heading (radians, 0 pointing along +x, y growing downwards)
+    // after travelling `distance` along the path, clamped to its ends
+    pub fn sample(&self, distance: f32) -> (Position, f32) {
+        let distance = distance.clamp(0.0, self.length());
+        let segment = match self.cumulative.iter().position(|&s| s > distance) {
+            Some(index) => index - 1,
+            None => self.points.len() - 2,
+        };
+
+        let (from, to) = (self.points[segment], self.points[segment + 1]);
+        let segment_length = self.cumulative[segment + 1] - self.cumulative[segment];
+        let t = if segment_length > 0.0 {
+            (distance - self.cumulative[segment]) / segment_length
+        } else {
+            0.0
+        };
+        let position = Position::new(from.x + (to.x - from.x) * t, from.y + (to.y - from.y) * t);
+        let heading = (to.y - from.y).atan2(to.x - from.x);
+        (position, heading)
+    }
+}
+
+// Direction of travel after performing `turn_direction` from `movement_direction`
+pub fn exit_direction(movement_direction: MovementDirection, turn_direction: TurnDirection) -> MovementDirection {
+    match turn_direction {
+        TurnDirection::Straight => movement_direction,
+        TurnDirection::Left => match movement_direction {
+            MovementDirection::Up => MovementDirection::Left,
+            MovementDirection::Down => MovementDirection::Right,
+            MovementDirection::Left => MovementDirection::Down,
+            MovementDirection::Right => MovementDirection::Up,
+        },
+        TurnDirection::Right => match movement_direction {
+            MovementDirection::Up => MovementDirection::Right,
+            MovementDirection::Down => MovementDirection::Left,
+            MovementDirection::Left => MovementDirection::Up,
+            MovementDirection::Right => MovementDirection::Down,
+        },
+    }
+}
+
+// Unit vector of a movement direction in screen coordinates
+pub fn direction_vector(movement_direction: MovementDirection) -> (f32, f32) {
+    match movement_direction {
+        MovementDirection::Up => (0.0, -1.0),
+        MovementDirection::Down => (0.0, 1.0),
+        MovementDirection::Left => (-1.0, 0.0),
+        MovementDirection::Right => (1.0, 0.0),
+    }
+}
+
+// Path through the intersection box for one movement: a straight line, or a
+// quarter-circle-like Bézier curve joining the entry and exit lanes tangentially
+pub fn turning_path(geometry: &Geometry, movement_direction: MovementDirection, turn_direction: TurnDirection) -> Path {
+    let entry = geometry.entry_point(movement_direction, turn_direction);
+    turning_path_from(geometry, entry, movement_direction, turn_direction, geometry.lane_index(turn_direction))
+}
+
+// Same as `turning_path`, starting from an arbitrary point on the box edge and
+// ending on lane `exit_lane` of the exit road
+pub fn turning_path_from(
+    geometry: &Geometry,
+    entry: Position,
+    movement_direction: MovementDirection,
+    turn_direction: TurnDirection,
+    exit_lane: usize,
+) -> Path {
+    let exit = geometry.exit_point_in_lane(movement_direction, turn_direction, exit_lane);
+    let (in_x, in_y) = direction_vector(movement_direction);
+    let lateral_shift = (exit.x - entry.x) * in_y - (exit.y - entry.y) * in_x;
+    if turn_direction == TurnDirection::Straight && lateral_shift.abs() < 0.01 {
+        return Path::line(entry, exit);
+    }
+
+    // Turns, and straight crossings that change lane on the way, follow a curve
+    let (out_x, out_y) = direction_vector(exit_direction(movement_direction, turn_direction));
+    // Leg lengths of the turn along the entry and exit directions
+    let entry_leg = ((exit.x - entry.x) * in_x + (exit.y - entry.y) * in_y).abs();
+    let exit_leg = ((exit.x - entry.x) * out_x + (exit.y - entry.y) * out_y).abs();
+
+    Path::cubic_bezier(
+        entry,
+        Position::new(
+            entry.x + in_x * entry_leg * QUARTER_CIRCLE_HANDLE,
+            entry.y + in_y * entry_leg * QUARTER_CIRCLE_HANDLE,
+        ),
+        Position::new(
+            exit.x - out_x * exit_leg * QUARTER_CIRCLE_HANDLE,
+            exit.y - out_y * exit_leg * QUARTER_CIRCLE_HANDLE,
+        ),
+        exit,
+    )
+}
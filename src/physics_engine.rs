@@ -1,5 +1,4 @@
-use crate::Vehicle;
-use crate::MovementDirection;
+use crate::vehicle::Vehicle;
 
 #[derive(Debug, PartialEq, Clone, Copy)]
 pub struct PhysicsEngine {
@@ -12,41 +11,45 @@ impl PhysicsEngine {
         PhysicsEngine { safety_distance, max_velocity }
     }
 
+    pub fn safety_distance(&self) -> f32 {
+        self.safety_distance
+    }
+
+    pub fn max_velocity(&self) -> f32 {
+        self.max_velocity
+    }
+
     // Update vehicle's position and speed based on elapsed time
     pub fn update(&self, vehicle: &mut Vehicle, elapsed_time: f32) {
         vehicle.velocity += vehicle.acceleration * elapsed_time;
         
         // Ensure velocity doesn't exceed max_velocity
         vehicle.velocity = vehicle.velocity.min(self.max_velocity);
-        
-        match vehicle.movement_direction {
-            MovementDirection::Up => {
-                vehicle.position.y -= vehicle.velocity * elapsed_time;
-            },
-            MovementDirection::Down => {
-                vehicle.position.y += vehicle.velocity * elapsed_time;
-            },
-            MovementDirection::Left => {
-                vehicle.position.x -= vehicle.velocity * elapsed_time;
-            },
-            MovementDirection::Right => {
-                vehicle.position.x += vehicle.velocity * elapsed_time;
-            }
+        vehicle.distance_travelled += vehicle.velocity * elapsed_time;
+
+        if vehicle.turn_progress.is_some() {
+            // Inside the intersection the vehicle follows its turning path by arc length
+            vehicle.advance_along_turn(vehicle.velocity * elapsed_time);
+            return;
         }
-        
-        vehicle.update_distance_and_time_to_intersection();
+
+        let (vx, vy) = vehicle.velocity_vector();
+        vehicle.position.x += vx * elapsed_time;
+        vehicle.position.y += vy * elapsed_time;
     }
 
-    // Adjust vehicle's speed to maintain safety distance from vehicle ahead
-    pub fn adjust_speed_for_safety(&self, vehicle: &Vehicle, vehicle_ahead: &Vehicle) -> f32 {
+    // Adjust vehicle's speed to maintain safety distance from vehicle ahead over the
+    // next `elapsed_time`
+    pub fn adjust_speed_for_safety(&self, vehicle: &Vehicle, vehicle_ahead: &Vehicle, elapsed_time: f32) -> f32 {
         let dx = vehicle_ahead.position.x - vehicle.position.x;
         let dy = vehicle_ahead.position.y - vehicle.position.y;
-        let distance_to_vehicle_ahead = (dx * dx + dy * dy).sqrt();
+        // Bumper-to-bumper gap, the safety distance is free road between the two vehicles
+        let distance_to_vehicle_ahead = (dx * dx + dy * dy).sqrt() - (vehicle.size + vehicle_ahead.size) / 2.0;
 
-        if distance_to_vehicle_ahead < self.safety_distance && vehicle.velocity > vehicle_ahead.velocity {
-            // If the vehicle ahead is closer than the safety distance and moving slower, match its speed
-            return vehicle_ahead.velocity;
-        }
-        return vehicle.velocity;
+        // Close at most the free road beyond the safety distance during one tick; inside
+        // the safety distance this means matching the speed of the vehicle ahead
+        let free_road = (distance_to_vehicle_ahead - self.safety_distance).max(0.0);
+        let max_safe_velocity = vehicle_ahead.velocity + free_road / elapsed_time;
+        vehicle.velocity.min(max_safe_velocity)
     }
 }
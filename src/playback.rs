@@ -0,0 +1,241 @@
+use crate::simulation::{MAX_TIME_SCALE, MIN_TIME_SCALE};
+use crate::trace::TraceFormat;
+use crate::vehicle::DEFAULT_VEHICLE_SIZE;
+use crate::Position;
+use std::collections::HashMap;
+use std::time::Duration;
+
+// One vehicle of a trace sample, as far as drawing it needs
+#[derive(Debug, Clone, Copy)]
+pub struct TracedVehicle {
+    pub id: i32,
+    pub position: Position,
+    pub heading: f32,
+    pub size: f32,
+}
+
+// All vehicles sampled at one simulation time
+#[derive(Debug, Clone)]
+pub struct TraceFrame {
+    pub time: Duration,
+    pub vehicles: Vec<TracedVehicle>,
+}
+
+// Plays back the trajectories of a trace written by `TraceWriter`, with its
+// own clock that can be paused, sped up and moved to any time. The trace must
+// contain the `id`, `time` and `position` fields; `heading` and `size` are used
+// when present.
+pub struct TracePlayback {
+    frames: Vec<TraceFrame>,
+    time: Duration,
+    speed: f32,
+    paused: bool,
+}
+
+impl TracePlayback {
+    pub fn new(mut frames: Vec<TraceFrame>) -> Result<Self, String> {
+        if frames.is_empty() {
+            return Err("trace contains no samples".to_string());
+        }
+        frames.sort_by_key(|frame| frame.time);
+        let time = frames[0].time;
+        Ok(TracePlayback { frames, time, speed: 1.0, paused: false })
+    }
+
+    pub fn load(path: &str) -> Result<Self, String> {
+        let text = std::fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+        let frames = match TraceFormat::for_path(path) {
+            TraceFormat::Csv => parse_csv(&text),
+            TraceFormat::JsonLines => parse_json_lines(&text),
+        };
+        frames.and_then(Self::new).map_err(|e| format!("{}: {}", path, e))
+    }
+
+    pub fn time(&self) -> Duration {
+        self.time
+    }
+
+    pub fn start_time(&self) -> Duration {
+        self.frames[0].time
+    }
+
+    pub fn end_time(&self) -> Duration {
+        self.frames[self.frames.len() - 1].time
+    }
+
+    pub fn speed(&self) -> f32 {
+        self.speed
+    }
+
+    pub fn set_speed(&mut self, speed: f32) {
+        self.speed = speed.clamp(MIN_TIME_SCALE, MAX_TIME_SCALE);
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    pub fn set_paused(&mut self, paused: bool) {
+        self.paused = paused;
+    }
+
+    // Jump to `time`, clamped to the span of the trace
+    pub fn seek(&mut self, time: Duration) {
+        self.time = time.clamp(self.start_time(), self.end_time());
+    }
+
+    // Jump by `seconds`, backwards when negative
+    pub fn seek_by(&mut self, seconds: f32) {
+        let time = if seconds >= 0.0 {
+            self.time + Duration::from_secs_f32(seconds)
+        } else {
+            self.time.saturating_sub(Duration::from_secs_f32(-seconds))
+        };
+        self.seek(time);
+    }
+
+    // Move the playback clock by `real_time` scaled by the speed; stops at the end
+    pub fn advance(&mut self, real_time: Duration) {
+        if !self.paused {
+            self.seek(self.time + real_time.mul_f32(self.speed));
+        }
+    }
+
+    // Latest sample at or before the playback time
+    pub fn current_frame(&self) -> &TraceFrame {
+        let index = self.frames.partition_point(|frame| frame.time <= self.time);
+        &self.frames[index.saturating_sub(1)]
+    }
+}
+
+// Collect the rows of a trace, in file order, into frames by time
+fn group_frames(samples: Vec<(Duration, TracedVehicle)>) -> Vec<TraceFrame> {
+    let mut frames: Vec<TraceFrame> = Vec::new();
+    let mut index_by_time: HashMap<Duration, usize> = HashMap::new();
+    for (time, vehicle) in samples {
+        let index = *index_by_time.entry(time).or_insert_with(|| {
+            frames.push(TraceFrame { time, vehicles: Vec::new() });
+            frames.len() - 1
+        });
+        frames[index].vehicles.push(vehicle);
+    }
+    frames
+}
+
+fn parse_csv(text: &str) -> Result<Vec<TraceFrame>, String> {
+    let mut lines = text.lines().enumerate().filter(|(_, line)| !line.trim().is_empty());
+    let header: Vec<&str> = match lines.next() {
+        Some((_, line)) => line.split(',').map(str::trim).collect(),
+        None => return Ok(Vec::new()),
+    };
+    let column = |name: &str| header.iter().position(|&column| column == name);
+    let required = |name: &str| column(name).ok_or_else(|| format!("trace has no '{}' column", name));
+    let (id, time, x, y) = (required("id")?, required("time")?, required("x")?, required("y")?);
+    let (heading, size) = (column("heading"), column("size"));
+
+    let mut samples = Vec::new();
+    for (index, line) in lines {
+        let line_number = index + 1;
+        let fields: Vec<&str> = line.split(',').map(str::trim).collect();
+        if fields.len() != header.len() {
+            return Err(format!("line {}: expected {} fields, found {}", line_number, header.len(), fields.len()));
+        }
+        let number = |column: usize| -> Result<f64, String> {
+            fields[column]
+                .parse()
+                .map_err(|_| format!("line {}: invalid {} '{}'", line_number, header[column], fields[column]))
+        };
+        samples.push((
+            trace_time(number(time)?, line_number)?,
+            TracedVehicle {
+                id: number(id)? as i32,
+                position: Position::new(number(x)? as f32, number(y)? as f32),
+                heading: heading.map(number).transpose()?.unwrap_or(0.0) as f32,
+                size: size.map(number).transpose()?.map_or(DEFAULT_VEHICLE_SIZE, |size| size as f32),
+            },
+        ));
+    }
+    Ok(group_frames(samples))
+}
+
+fn parse_json_lines(text: &str) -> Result<Vec<TraceFrame>, String> {
+    let mut samples = Vec::new();
+    for (index, line) in text.lines().enumerate() {
+        let line_number = index + 1;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let value: serde_json::Value = serde_json::from_str(line).map_err(|e| format!("line {}: {}", line_number, e))?;
+        let number = |name: &str| value.get(name).and_then(serde_json::Value::as_f64);
+        let required = |name: &str| number(name).ok_or_else(|| format!("line {}: missing number '{}'", line_number, name));
+        samples.push((
+            trace_time(required("time")?, line_number)?,
+            TracedVehicle {
+                id: required("id")? as i32,
+                position: Position::new(required("x")? as f32, required("y")? as f32),
+                heading: number("heading").unwrap_or(0.0) as f32,
+                size: number("size").map_or(DEFAULT_VEHICLE_SIZE, |size| size as f32),
+            },
+        ));
+    }
+    Ok(group_frames(samples))
+}
+
+fn trace_time(seconds: f64, line_number: usize) -> Result<Duration, String> {
+    Duration::try_from_secs_f64(seconds).map_err(|_| format!("line {}: invalid time {}", line_number, seconds))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CSV: &str = "id,time,x,y,heading\n1,0.5000,10.00,20.00,1.5708\n2,0.5000,30.00,40.00,0.0000\n1,1.0000,10.00,35.00,1.5708\n";
+
+    const JSON_LINES: &str = concat!(
+        r#"{"id":1,"time":0.5,"x":10.0,"y":20.0,"heading":1.5708}"#,
+        "\n",
+        r#"{"id":2,"time":0.5,"x":30.0,"y":40.0,"heading":0.0}"#,
+        "\n\n",
+        r#"{"id":1,"time":1.0,"x":10.0,"y":35.0,"heading":1.5708}"#,
+        "\n",
+    );
+
+    #[test]
+    fn both_formats_give_the_same_frames() {
+        let frames = parse_csv(CSV).unwrap();
+        assert_eq!(format!("{:?}", frames), format!("{:?}", parse_json_lines(JSON_LINES).unwrap()));
+        assert_eq!(frames.len(), 2);
+        assert_eq!(frames[0].vehicles.len(), 2);
+        assert_eq!(frames[0].vehicles[1].size, DEFAULT_VEHICLE_SIZE);
+    }
+
+    #[test]
+    fn missing_or_broken_columns_are_reported() {
+        assert_eq!(parse_csv("id,time,x\n1,0.5,10\n").err(), Some("trace has no 'y' column".to_string()));
+        assert_eq!(parse_csv("id,time,x,y\n1,0.5,10\n").err(), Some("line 2: expected 4 fields, found 3".to_string()));
+        assert_eq!(parse_csv("id,time,x,y\n1,soon,10,20\n").err(), Some("line 2: invalid time 'soon'".to_string()));
+        assert_eq!(parse_json_lines(r#"{"id":1,"time":0.5,"x":10}"#).err(), Some("line 1: missing number 'y'".to_string()));
+        assert!(TracePlayback::new(Vec::new()).is_err());
+    }
+
+    #[test]
+    fn playback_shows_the_latest_frame_within_the_trace() {
+        let mut playback = TracePlayback::new(parse_csv(CSV).unwrap()).unwrap();
+        assert_eq!(playback.time(), Duration::from_millis(500));
+
+        playback.advance(Duration::from_millis(400));
+        assert_eq!(playback.current_frame().time, Duration::from_millis(500));
+        playback.set_speed(2.0);
+        playback.advance(Duration::from_millis(100));
+        assert_eq!(playback.current_frame().time, Duration::from_secs(1));
+
+        playback.seek(Duration::from_secs(5));
+        assert_eq!(playback.time(), playback.end_time());
+        playback.seek_by(-10.0);
+        assert_eq!(playback.time(), playback.start_time());
+
+        playback.set_paused(true);
+        playback.advance(Duration::from_secs(1));
+        assert_eq!(playback.time(), playback.start_time());
+    }
+}
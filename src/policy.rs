@@ -0,0 +1,901 @@
+use crate::conflict_matrix::{ConflictMatrix, Movement};
+use crate::geometry::Geometry;
+use crate::intersection_manager::{PreemptionRule, Reservation, ReservationCounts};
+use crate::path::Path;
+use crate::spawner::APPROACHES;
+use crate::tiles::TileGrid;
+use crate::vehicle::Vehicle;
+use crate::MovementDirection;
+use std::collections::HashSet;
+use std::time::Duration;
+
+// Vehicles ask for a reservation once their front is this close to the stop line,
+// unless the policy asks for less, see `IntersectionPolicy::request_distance`
+pub const RESERVATION_DISTANCE: f32 = 150.0;
+// Time between two rounds of the batch policy
+pub const BATCH_INTERVAL: Duration = Duration::from_secs(1);
+// Green time of each approach of the fixed-time signal, followed by an all-red
+// clearance so the last vehicles leave the box before the next approach starts
+pub const SIGNAL_GREEN: Duration = Duration::from_secs(8);
+pub const SIGNAL_ALL_RED: Duration = Duration::from_secs(2);
+// How long a vehicle stands at an all-way stop before it may go
+pub const STOP_DURATION: Duration = Duration::from_secs(1);
+// A vehicle that stopped asking this long ago is no longer queued at the stop
+const STOP_QUEUE_TIMEOUT: Duration = Duration::from_secs(1);
+// The all-way stop line lies this far before the box, so a vehicle turning from
+// the next lane swings its rear clear of the vehicles standing there
+const STOP_LINE_SETBACK: f32 = 50.0;
+// Vehicles count as standing at the all-way stop line once their front rests this
+// close to it; the simulation brings them to rest on it, see `stop_line`
+const STOP_LINE_TOLERANCE: f32 = 10.0;
+// A vehicle due in the box sooner than this is too close to stop clear of
+// vehicles turning next to it, so its reservation is no longer revoked
+const REVOCATION_NOTICE: Duration = Duration::from_millis(100);
+
+// Intersection control algorithm a scenario can select
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum PolicyKind {
+    // First come, first served reservations of space-time tiles (AIM)
+    Fcfs,
+    // First come, first served reservations of the whole box for the crossing time
+    WholeBox,
+    // Requests collected for `BATCH_INTERVAL`, then granted by priority and arrival
+    Batch,
+    // Fixed-time signal giving each approach green in turn
+    Signal,
+    // Every vehicle stops, then vehicles go in the order they arrived
+    AllWayStop,
+}
+
+impl PolicyKind {
+    pub const ALL: [PolicyKind; 5] =
+        [PolicyKind::Fcfs, PolicyKind::WholeBox, PolicyKind::Batch, PolicyKind::Signal, PolicyKind::AllWayStop];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            PolicyKind::Fcfs => "fcfs",
+            PolicyKind::WholeBox => "whole_box",
+            PolicyKind::Batch => "batch",
+            PolicyKind::Signal => "signal",
+            PolicyKind::AllWayStop => "all_way_stop",
+        }
+    }
+
+    // "aim" is accepted for `Fcfs`, which is the AIM scheme
+    pub fn parse(name: &str) -> Option<Self> {
+        let name = name.to_ascii_lowercase();
+        if name == "aim" {
+            return Some(PolicyKind::Fcfs);
+        }
+        Self::ALL.into_iter().find(|policy| policy.name() == name)
+    }
+
+    // Same as `parse`, with an error listing the known policies
+    pub fn from_name(name: &str) -> Result<Self, String> {
+        Self::parse(name).ok_or_else(|| {
+            let known: Vec<&str> = Self::ALL.iter().map(PolicyKind::name).collect();
+            format!("unknown policy '{}', expected one of {}", name, known.join(", "))
+        })
+    }
+
+    pub fn build(&self) -> Box<dyn IntersectionPolicy> {
+        match self {
+            PolicyKind::Fcfs => Box::new(FcfsPolicy::default()),
+            PolicyKind::WholeBox => Box::new(WholeBoxPolicy::default()),
+            PolicyKind::Batch => Box::new(BatchPolicy::default()),
+            PolicyKind::Signal => Box::new(SignalPolicy::default()),
+            PolicyKind::AllWayStop => Box::new(AllWayStopPolicy::default()),
+        }
+    }
+}
+
+// Decides which vehicles may enter one intersection box. The manager owning the
+// policy passes in the intersection's layout and settings with every call.
+pub trait IntersectionPolicy {
+    fn kind(&self) -> PolicyKind;
+
+    // Ask for permission to cross on the vehicle's current plan. Denied vehicles
+    // stop and ask again on the next tick.
+    fn request(&mut self, context: &PolicyContext, vehicle: &Vehicle, now: Duration) -> Result<(), &'static str>;
+
+    // Advance to `now`: expire old reservations, switch phases, run batches
+    fn tick(&mut self, context: &PolicyContext, now: Duration);
+
+    fn book(&self) -> &ReservationBook;
+    fn book_mut(&mut self) -> &mut ReservationBook;
+
+    // The vehicle gives up its permission before crossing. False when it held none.
+    fn cancel(&mut self, vehicle_id: i32) -> bool {
+        self.book_mut().cancel(vehicle_id)
+    }
+
+    // The vehicle left the box. False when it held no permission.
+    fn release(&mut self, vehicle_id: i32) -> bool {
+        self.book_mut().release(vehicle_id)
+    }
+
+    // Permission the vehicle currently holds
+    fn query(&self, vehicle_id: i32) -> Option<&Reservation> {
+        self.book().get(vehicle_id)
+    }
+
+    // Distance of the front bumper to the stop line from which vehicles ask
+    fn request_distance(&self) -> f32 {
+        RESERVATION_DISTANCE
+    }
+
+    // Distance of the stop line to the box where vehicles without permission come to
+    // rest, for policies that need them standing there; others stop where they are told
+    fn stop_line(&self) -> Option<f32> {
+        None
+    }
+}
+
+// Layout and settings of an intersection shared by every policy
+pub struct PolicyContext {
+    pub geometry: Geometry,
+    pub tile_grid: TileGrid,
+    pub conflict_matrix: ConflictMatrix,
+    pub preemption_rule: PreemptionRule,
+}
+
+impl PolicyContext {
+    // Speed a reservation assumes; a vehicle held at the stop line would enter
+    // at its cruising speed once granted
+    fn crossing_speed(vehicle: &Vehicle) -> f32 {
+        if vehicle.velocity > 0.0 { vehicle.velocity } else { vehicle.desired_velocity }
+    }
+
+    pub fn calculate_reservation_window(&self, vehicle: &Vehicle, now: Duration) -> (Duration, Duration) {
+        let speed = Self::crossing_speed(vehicle);
+        let entry_time = now + Duration::from_secs_f32(vehicle.distance_to_intersection / speed);
+        // The box is occupied from the front bumper crossing the stop line
+        // until the rear bumper leaves at the end of the turning path
+        let path_length = vehicle.planned_path(&self.geometry).length();
+        let time_to_cross = (path_length + vehicle.size) / speed;
+        let exit_time = entry_time + Duration::from_secs_f32(time_to_cross);
+        (entry_time, exit_time)
+    }
+
+    // Reservation of the whole box for the time `vehicle` needs to cross it if it
+    // keeps its speed from `now` on; it claims no tiles
+    pub fn window_reservation(&self, vehicle: &Vehicle, now: Duration) -> Reservation {
+        let (start_time, end_time) = self.calculate_reservation_window(vehicle, now);
+        Reservation {
+            vehicle_id: vehicle.id,
+            turn_direction: vehicle.turn_direction(),
+            movement_direction: vehicle.movement_direction(),
+            vehicle_lane: vehicle.lane,
+            start_time,
+            end_time,
+            tiles: HashSet::new(),
+            priority: vehicle.priority,
+        }
+    }
+
+    // Same as `window_reservation`, claiming the tiles swept by `vehicle` along its
+    // planned path
+    pub fn proposed_reservation(&self, vehicle: &Vehicle, now: Duration) -> Reservation {
+        let mut reservation = self.window_reservation(vehicle, now);
+        let path = vehicle.planned_path(&self.geometry);
+        reservation.tiles =
+            self.tile_grid.claims(&path, vehicle.size, reservation.start_time, Self::crossing_speed(vehicle));
+        reservation
+    }
+
+    fn movements_conflict(&self, first: &Reservation, second: &Reservation) -> bool {
+        self.conflict_matrix.conflicts(
+            (first.movement_direction, first.turn_direction),
+            (second.movement_direction, second.turn_direction),
+        )
+    }
+
+    // Two whole-box reservations conflict when their movements may meet and their
+    // windows, widened by the time margin, overlap
+    pub fn windows_conflict(&self, proposed: &Reservation, existing_reservation: &Reservation) -> bool {
+        let margin = self.tile_grid.config().time_margin;
+        self.movements_conflict(proposed, existing_reservation)
+            && proposed.start_time < existing_reservation.end_time + margin
+            && existing_reservation.start_time < proposed.end_time + margin
+    }
+
+    // Two crossings conflict when their movements may meet and they claim the
+    // same tile during the same slot
+    pub fn has_conflict(&self, proposed: &Reservation, existing_reservation: &Reservation) -> bool {
+        if !self.movements_conflict(proposed, existing_reservation) {
+            return false;
+        }
+        // Claims extend past the window by the time margin plus the slot they fall in
+        let config = self.tile_grid.config();
+        let reach = config.time_margin + config.slot;
+        if proposed.end_time + reach < existing_reservation.start_time
+            || existing_reservation.end_time + reach < proposed.start_time
+        {
+            return false;
+        }
+        let (smaller, larger) = if proposed.tiles.len() <= existing_reservation.tiles.len() {
+            (&proposed.tiles, &existing_reservation.tiles)
+        } else {
+            (&existing_reservation.tiles, &proposed.tiles)
+        };
+        smaller.iter().any(|claim| larger.contains(claim))
+    }
+
+    // A granted reservation only gives way under the preemption rule, never once
+    // its vehicle may already be in the box and never to a vehicle queued behind
+    // it in the same lane, which could not pass it anyway
+    pub fn may_preempt(&self, proposed: &Reservation, existing_reservation: &Reservation, now: Duration) -> bool {
+        let same_lane = proposed.movement_direction == existing_reservation.movement_direction
+            && proposed.vehicle_lane == existing_reservation.vehicle_lane;
+        match self.preemption_rule {
+            PreemptionRule::Never => false,
+            PreemptionRule::HigherPriority => {
+                proposed.priority > existing_reservation.priority && existing_reservation.start_time > now + REVOCATION_NOTICE && !same_lane
+            }
+        }
+    }
+}
+
+// Granted reservations of one intersection and what became of earlier ones
+#[derive(Default)]
+pub struct ReservationBook {
+    reservations: Vec<Reservation>,
+    // Vehicles whose reservation was revoked since the last `take_revocations`
+    revocations: Vec<i32>,
+    expired_count: usize,
+    cancelled_count: usize,
+    revoked_count: usize,
+}
+
+impl ReservationBook {
+    pub fn reservations(&self) -> &[Reservation] {
+        &self.reservations
+    }
+
+    pub fn get(&self, vehicle_id: i32) -> Option<&Reservation> {
+        self.reservations.iter().find(|reservation| reservation.vehicle_id == vehicle_id)
+    }
+
+    pub fn counts(&self) -> ReservationCounts {
+        ReservationCounts {
+            active: self.reservations.len(),
+            expired: self.expired_count,
+            cancelled: self.cancelled_count,
+            revoked: self.revoked_count,
+        }
+    }
+
+    // Grant `reservation` unless it conflicts with one already granted that it may
+    // not preempt. Preempted reservations are revoked; a new grant replaces the
+    // vehicle's previous reservation, if any.
+    pub fn reserve(
+        &mut self,
+        reservation: Reservation,
+        conflicts: impl Fn(&Reservation, &Reservation) -> bool,
+        may_preempt: impl Fn(&Reservation, &Reservation) -> bool,
+    ) -> Result<(), &'static str> {
+        let mut to_revoke = Vec::new();
+        for (index, existing_reservation) in self.reservations.iter().enumerate() {
+            if existing_reservation.vehicle_id == reservation.vehicle_id {
+                continue; // never conflicts with itself
+            }
+            if conflicts(&reservation, existing_reservation) {
+                if may_preempt(&reservation, existing_reservation) {
+                    to_revoke.push(index);
+                } else {
+                    return Err("Reservation conflict");
+                }
+            }
+        }
+
+        for index in to_revoke.iter().rev() {
+            let revoked = self.reservations.remove(*index);
+            self.revocations.push(revoked.vehicle_id);
+        }
+        self.revoked_count += to_revoke.len();
+
+        self.remove(reservation.vehicle_id);
+        self.reservations.push(reservation);
+        Ok(())
+    }
+
+    // Drop the reservations whose window ended before `now`
+    pub fn expire(&mut self, now: Duration) {
+        let before = self.reservations.len();
+        self.reservations.retain(|reservation| reservation.end_time >= now);
+        self.expired_count += before - self.reservations.len();
+    }
+
+    // The vehicle left the box; its reservation expires early
+    pub fn release(&mut self, vehicle_id: i32) -> bool {
+        let released = self.remove(vehicle_id);
+        if released {
+            self.expired_count += 1;
+        }
+        released
+    }
+
+    pub fn cancel(&mut self, vehicle_id: i32) -> bool {
+        let cancelled = self.remove(vehicle_id);
+        if cancelled {
+            self.cancelled_count += 1;
+        }
+        cancelled
+    }
+
+    // Vehicles that lost their reservation to a preempting request since the last
+    // call; they have to stop before the box and ask again
+    pub fn take_revocations(&mut self) -> Vec<i32> {
+        std::mem::take(&mut self.revocations)
+    }
+
+    fn remove(&mut self, vehicle_id: i32) -> bool {
+        match self.reservations.iter().position(|reservation| reservation.vehicle_id == vehicle_id) {
+            Some(index) => {
+                self.reservations.remove(index);
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+// A vehicle that never moves has no finite arrival time to reserve
+fn check_moving(vehicle: &Vehicle) -> Result<(), &'static str> {
+    if vehicle.velocity <= 0.0 && vehicle.desired_velocity <= 0.0 {
+        return Err("Vehicle is not moving");
+    }
+    Ok(())
+}
+
+#[derive(Default)]
+pub struct WholeBoxPolicy {
+    book: ReservationBook,
+}
+
+impl IntersectionPolicy for WholeBoxPolicy {
+    fn kind(&self) -> PolicyKind {
+        PolicyKind::WholeBox
+    }
+
+    fn request(&mut self, context: &PolicyContext, vehicle: &Vehicle, now: Duration) -> Result<(), &'static str> {
+        check_moving(vehicle)?;
+        let reservation = context.window_reservation(vehicle, now);
+        self.book.reserve(reservation, |a, b| context.windows_conflict(a, b), |a, b| context.may_preempt(a, b, now))
+    }
+
+    fn tick(&mut self, _context: &PolicyContext, now: Duration) {
+        self.book.expire(now);
+    }
+
+    fn book(&self) -> &ReservationBook {
+        &self.book
+    }
+
+    fn book_mut(&mut self) -> &mut ReservationBook {
+        &mut self.book
+    }
+}
+
+#[derive(Default)]
+pub struct FcfsPolicy {
+    book: ReservationBook,
+}
+
+impl IntersectionPolicy for FcfsPolicy {
+    fn kind(&self) -> PolicyKind {
+        PolicyKind::Fcfs
+    }
+
+    fn request(&mut self, context: &PolicyContext, vehicle: &Vehicle, now: Duration) -> Result<(), &'static str> {
+        check_moving(vehicle)?;
+        let reservation = context.proposed_reservation(vehicle, now);
+        self.book.reserve(reservation, |a, b| context.has_conflict(a, b), |a, b| context.may_preempt(a, b, now))
+    }
+
+    fn tick(&mut self, _context: &PolicyContext, now: Duration) {
+        self.book.expire(now);
+    }
+
+    fn book(&self) -> &ReservationBook {
+        &self.book
+    }
+
+    fn book_mut(&mut self) -> &mut ReservationBook {
+        &mut self.book
+    }
+}
+
+// Tile reservations granted in rounds instead of on arrival, so a round can
+// favour high priority vehicles and fill the box with compatible movements.
+// Waiting vehicles ask every tick, which keeps their pending request current.
+#[derive(Default)]
+pub struct BatchPolicy {
+    book: ReservationBook,
+    pending: Vec<PendingRequest>,
+    next_batch: Duration,
+}
+
+// Request waiting for the next round. Its tiles are only claimed when the round
+// runs, not on each of the many ticks it is renewed.
+struct PendingRequest {
+    reservation: Reservation,
+    path: Path,
+    size: f32,
+    speed: f32,
+}
+
+impl IntersectionPolicy for BatchPolicy {
+    fn kind(&self) -> PolicyKind {
+        PolicyKind::Batch
+    }
+
+    fn request(&mut self, context: &PolicyContext, vehicle: &Vehicle, now: Duration) -> Result<(), &'static str> {
+        check_moving(vehicle)?;
+        if self.book.get(vehicle.id).is_some() {
+            return Ok(()); // granted in the last round
+        }
+        self.pending.retain(|request| request.reservation.vehicle_id != vehicle.id);
+        self.pending.push(PendingRequest {
+            reservation: context.window_reservation(vehicle, now),
+            path: vehicle.planned_path(&context.geometry),
+            size: vehicle.size,
+            speed: PolicyContext::crossing_speed(vehicle),
+        });
+        Err("Waiting for the next batch")
+    }
+
+    fn tick(&mut self, context: &PolicyContext, now: Duration) {
+        self.book.expire(now);
+        if now < self.next_batch {
+            return;
+        }
+        self.next_batch = now + BATCH_INTERVAL;
+
+        let mut pending = std::mem::take(&mut self.pending);
+        pending.sort_by(|a, b| {
+            let (a, b) = (&a.reservation, &b.reservation);
+            b.priority.cmp(&a.priority).then(a.start_time.cmp(&b.start_time))
+        });
+        for request in pending {
+            let mut reservation = request.reservation;
+            reservation.tiles =
+                context.tile_grid.claims(&request.path, request.size, reservation.start_time, request.speed);
+            // Requests that do not fit wait for the next round
+            let _ = self.book.reserve(reservation, |a, b| context.has_conflict(a, b), |a, b| context.may_preempt(a, b, now));
+        }
+    }
+
+    fn cancel(&mut self, vehicle_id: i32) -> bool {
+        self.pending.retain(|request| request.reservation.vehicle_id != vehicle_id);
+        self.book.cancel(vehicle_id)
+    }
+
+    fn book(&self) -> &ReservationBook {
+        &self.book
+    }
+
+    fn book_mut(&mut self) -> &mut ReservationBook {
+        &mut self.book
+    }
+}
+
+// Fixed-time signal with one phase per approach, in `APPROACHES` order. Vehicles
+// are let in while their approach is green if they reach the box before it
+// turns red; whole-box reservations keep movements of the same approach that
+// may meet apart. Priorities are ignored.
+#[derive(Default)]
+pub struct SignalPolicy {
+    book: ReservationBook,
+}
+
+impl SignalPolicy {
+    // Approach that has green at `now` and when its green ends, None during all-red
+    pub fn green(now: Duration) -> Option<(MovementDirection, Duration)> {
+        let phase_length = SIGNAL_GREEN + SIGNAL_ALL_RED;
+        let cycle = phase_length * APPROACHES.len() as u32;
+        let in_cycle = Duration::from_nanos((now.as_nanos() % cycle.as_nanos()) as u64);
+        let phase = (in_cycle.as_nanos() / phase_length.as_nanos()) as usize;
+        let in_phase = in_cycle - phase_length * phase as u32;
+        if in_phase < SIGNAL_GREEN {
+            Some((APPROACHES[phase], now + (SIGNAL_GREEN - in_phase)))
+        } else {
+            None
+        }
+    }
+}
+
+impl IntersectionPolicy for SignalPolicy {
+    fn kind(&self) -> PolicyKind {
+        PolicyKind::Signal
+    }
+
+    fn request(&mut self, context: &PolicyContext, vehicle: &Vehicle, now: Duration) -> Result<(), &'static str> {
+        check_moving(vehicle)?;
+        let reservation = context.window_reservation(vehicle, now);
+        match Self::green(now) {
+            Some((approach, green_end))
+                if approach == vehicle.movement_direction() && reservation.start_time < green_end =>
+            {
+                self.book.reserve(reservation, |a, b| context.windows_conflict(a, b), |_, _| false)
+            }
+            _ => Err("Red light"),
+        }
+    }
+
+    fn tick(&mut self, _context: &PolicyContext, now: Duration) {
+        self.book.expire(now);
+    }
+
+    fn book(&self) -> &ReservationBook {
+        &self.book
+    }
+
+    fn book_mut(&mut self) -> &mut ReservationBook {
+        &mut self.book
+    }
+}
+
+// Vehicle standing at an all-way stop
+struct StopArrival {
+    vehicle_id: i32,
+    movement: Movement,
+    // When the vehicle came to a stop at the line, which orders the queue
+    arrived: Duration,
+    last_request: Duration,
+}
+
+// All-way stop: vehicles only ask once they reach the stop line and are turned
+// away until they stand there. Each may go after standing for `STOP_DURATION`
+// once no vehicle that stopped earlier with a movement it may meet is still
+// waiting. Whole-box reservations keep the vehicles allowed to go apart.
+// Priorities are ignored.
+#[derive(Default)]
+pub struct AllWayStopPolicy {
+    book: ReservationBook,
+    arrivals: Vec<StopArrival>,
+}
+
+impl IntersectionPolicy for AllWayStopPolicy {
+    fn kind(&self) -> PolicyKind {
+        PolicyKind::AllWayStop
+    }
+
+    fn request(&mut self, context: &PolicyContext, vehicle: &Vehicle, now: Duration) -> Result<(), &'static str> {
+        check_moving(vehicle)?;
+        let to_line = vehicle.distance_to_intersection - STOP_LINE_SETBACK;
+        let at_line = to_line.abs() <= STOP_LINE_TOLERANCE && vehicle.velocity == 0.0;
+        let index = match self.arrivals.iter().position(|arrival| arrival.vehicle_id == vehicle.id) {
+            Some(index) => index,
+            None if !at_line => return Err("Stopping"),
+            None => {
+                self.arrivals.push(StopArrival {
+                    vehicle_id: vehicle.id,
+                    movement: (vehicle.movement_direction(), vehicle.turn_direction()),
+                    arrived: now,
+                    last_request: now,
+                });
+                return Err("Stopping");
+            }
+        };
+        self.arrivals[index].last_request = now;
+        let arrival = &self.arrivals[index];
+        if now < arrival.arrived + STOP_DURATION {
+            return Err("Stopping");
+        }
+        let yields = self.arrivals.iter().any(|other| {
+            other.arrived < arrival.arrived && context.conflict_matrix.conflicts(other.movement, arrival.movement)
+        });
+        if yields {
+            return Err("Yielding to an earlier arrival");
+        }
+
+        let reservation = context.window_reservation(vehicle, now);
+        self.book.reserve(reservation, |a, b| context.windows_conflict(a, b), |_, _| false)?;
+        self.arrivals.remove(index);
+        Ok(())
+    }
+
+    fn tick(&mut self, _context: &PolicyContext, now: Duration) {
+        self.book.expire(now);
+        self.arrivals.retain(|arrival| arrival.last_request + STOP_QUEUE_TIMEOUT >= now);
+    }
+
+    fn request_distance(&self) -> f32 {
+        STOP_LINE_SETBACK + STOP_LINE_TOLERANCE
+    }
+
+    fn stop_line(&self) -> Option<f32> {
+        Some(STOP_LINE_SETBACK)
+    }
+
+    fn cancel(&mut self, vehicle_id: i32) -> bool {
+        self.arrivals.retain(|arrival| arrival.vehicle_id != vehicle_id);
+        self.book.cancel(vehicle_id)
+    }
+
+    fn book(&self) -> &ReservationBook {
+        &self.book
+    }
+
+    fn book_mut(&mut self) -> &mut ReservationBook {
+        &mut self.book
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::path::direction_vector;
+    use crate::tiles::TileConfig;
+    use crate::vehicle::Lane;
+    use crate::Position;
+    use crate::TurnDirection;
+
+    const SPEED: f32 = 600.0;
+
+    fn context(preemption_rule: PreemptionRule) -> PolicyContext {
+        let geometry = Geometry::default();
+        PolicyContext {
+            tile_grid: TileGrid::new(&geometry, TileConfig::default()),
+            conflict_matrix: ConflictMatrix::from_geometry(&geometry),
+            preemption_rule,
+            geometry,
+        }
+    }
+
+    // Vehicle `distance` before the box in the lane of its turn, cruising unless `velocity` is 0
+    fn vehicle(id: i32, movement: Movement, distance: f32, velocity: f32) -> Vehicle {
+        let (movement_direction, turn_direction) = movement;
+        let entry = Geometry::default().entry_point(movement_direction, turn_direction);
+        let (dx, dy) = direction_vector(movement_direction);
+        let back = distance + crate::vehicle::DEFAULT_VEHICLE_SIZE / 2.0;
+        let position = Position::new(entry.x - dx * back, entry.y - dy * back);
+        let lane = Lane::for_turn(turn_direction);
+        let mut vehicle = Vehicle::new(movement_direction, turn_direction, velocity, position, lane);
+        vehicle.id = id;
+        vehicle.desired_velocity = SPEED;
+        vehicle.distance_to_intersection = distance;
+        vehicle
+    }
+
+    const DOWN_STRAIGHT: Movement = (MovementDirection::Down, TurnDirection::Straight);
+    const UP_STRAIGHT: Movement = (MovementDirection::Up, TurnDirection::Straight);
+    const RIGHT_STRAIGHT: Movement = (MovementDirection::Right, TurnDirection::Straight);
+
+    fn secs(seconds: f32) -> Duration {
+        Duration::from_secs_f32(seconds)
+    }
+
+    #[test]
+    fn names_parse_back() {
+        for kind in PolicyKind::ALL {
+            assert_eq!(PolicyKind::parse(kind.name()), Some(kind));
+            assert_eq!(kind.build().kind(), kind);
+        }
+        assert_eq!(PolicyKind::parse("AIM"), Some(PolicyKind::Fcfs));
+        assert!(PolicyKind::from_name("roundabout").is_err());
+    }
+
+    #[test]
+    fn reservation_policies_grant_first_come_first_served() {
+        let context = context(PreemptionRule::Never);
+        for kind in [PolicyKind::Fcfs, PolicyKind::WholeBox] {
+            let mut policy = kind.build();
+            assert_eq!(policy.request(&context, &vehicle(1, DOWN_STRAIGHT, 100.0, SPEED), Duration::ZERO), Ok(()));
+            // Same time, crossing movement
+            let crossing = vehicle(2, RIGHT_STRAIGHT, 100.0, SPEED);
+            assert_eq!(policy.request(&context, &crossing, Duration::ZERO), Err("Reservation conflict"), "{:?}", kind);
+            // Same time, a movement that never meets the first
+            assert_eq!(policy.request(&context, &vehicle(3, UP_STRAIGHT, 100.0, SPEED), Duration::ZERO), Ok(()));
+            // Well after the first has left the box
+            assert_eq!(policy.request(&context, &vehicle(2, RIGHT_STRAIGHT, 1000.0, SPEED), Duration::ZERO), Ok(()));
+            assert_eq!(policy.book().reservations().len(), 3);
+        }
+    }
+
+    #[test]
+    fn reservations_expire_and_are_released() {
+        let context = context(PreemptionRule::Never);
+        let mut policy = PolicyKind::Fcfs.build();
+        policy.request(&context, &vehicle(1, DOWN_STRAIGHT, 100.0, SPEED), Duration::ZERO).unwrap();
+        policy.request(&context, &vehicle(2, UP_STRAIGHT, 100.0, SPEED), Duration::ZERO).unwrap();
+        policy.request(&context, &vehicle(3, DOWN_STRAIGHT, 2000.0, SPEED), Duration::ZERO).unwrap();
+
+        assert!(policy.release(1));
+        assert!(!policy.release(1));
+        assert!(policy.cancel(2));
+        policy.tick(&context, secs(60.0));
+        assert_eq!(
+            policy.book().counts(),
+            ReservationCounts { active: 0, expired: 2, cancelled: 1, revoked: 0 }
+        );
+    }
+
+    #[test]
+    fn granted_reservations_are_binding_without_preemption() {
+        let context = context(PreemptionRule::Never);
+        let mut policy = PolicyKind::Fcfs.build();
+        policy.request(&context, &vehicle(1, DOWN_STRAIGHT, 100.0, SPEED), Duration::ZERO).unwrap();
+
+        let mut ambulance = vehicle(2, RIGHT_STRAIGHT, 100.0, SPEED);
+        ambulance.priority = 1;
+        assert_eq!(policy.request(&context, &ambulance, Duration::ZERO), Err("Reservation conflict"));
+        assert!(policy.book_mut().take_revocations().is_empty());
+    }
+
+    #[test]
+    fn higher_priority_revokes_reservations_not_due_yet() {
+        let context = context(PreemptionRule::HigherPriority);
+        for kind in [PolicyKind::Fcfs, PolicyKind::WholeBox] {
+            let mut policy = kind.build();
+            policy.request(&context, &vehicle(1, DOWN_STRAIGHT, 100.0, SPEED), Duration::ZERO).unwrap();
+
+            let mut ambulance = vehicle(2, RIGHT_STRAIGHT, 100.0, SPEED);
+            ambulance.priority = 1;
+            assert_eq!(policy.request(&context, &ambulance, Duration::ZERO), Ok(()), "{:?}", kind);
+            assert_eq!(policy.book_mut().take_revocations(), vec![1]);
+            assert!(policy.query(1).is_none());
+            assert_eq!(policy.book().counts().revoked, 1);
+
+            // A lower priority does not preempt
+            let regular = vehicle(3, DOWN_STRAIGHT, 100.0, SPEED);
+            assert_eq!(policy.request(&context, &regular, Duration::ZERO), Err("Reservation conflict"));
+        }
+    }
+
+    #[test]
+    fn reservations_about_to_start_are_not_revoked() {
+        let context = context(PreemptionRule::HigherPriority);
+        let mut policy = PolicyKind::Fcfs.build();
+        // Due in the box well within the revocation notice
+        policy.request(&context, &vehicle(1, DOWN_STRAIGHT, 10.0, SPEED), Duration::ZERO).unwrap();
+
+        let mut ambulance = vehicle(2, RIGHT_STRAIGHT, 10.0, SPEED);
+        ambulance.priority = 1;
+        assert_eq!(policy.request(&context, &ambulance, Duration::ZERO), Err("Reservation conflict"));
+        assert!(policy.book_mut().take_revocations().is_empty());
+    }
+
+    #[test]
+    fn stopped_vehicles_without_a_speed_are_refused() {
+        let context = context(PreemptionRule::Never);
+        let mut parked = vehicle(1, DOWN_STRAIGHT, 100.0, 0.0);
+        parked.desired_velocity = 0.0;
+        for kind in PolicyKind::ALL {
+            assert_eq!(kind.build().request(&context, &parked, Duration::ZERO), Err("Vehicle is not moving"));
+        }
+    }
+
+    #[test]
+    fn batch_grants_in_rounds_by_priority() {
+        let context = context(PreemptionRule::Never);
+        let mut policy = PolicyKind::Batch.build();
+        let first = vehicle(1, DOWN_STRAIGHT, 100.0, SPEED);
+        let mut ambulance = vehicle(2, RIGHT_STRAIGHT, 100.0, SPEED);
+        ambulance.priority = 1;
+
+        assert_eq!(policy.request(&context, &first, Duration::ZERO), Err("Waiting for the next batch"));
+        assert_eq!(policy.request(&context, &ambulance, Duration::ZERO), Err("Waiting for the next batch"));
+        policy.tick(&context, Duration::ZERO);
+
+        // The ambulance wins the round even though it asked second
+        assert_eq!(policy.request(&context, &ambulance, secs(0.1)), Ok(()));
+        assert_eq!(policy.request(&context, &first, secs(0.1)), Err("Waiting for the next batch"));
+
+        // No new round before `BATCH_INTERVAL` has passed
+        policy.tick(&context, BATCH_INTERVAL / 2);
+        assert!(policy.query(1).is_none());
+        policy.tick(&context, BATCH_INTERVAL);
+        assert!(policy.query(1).is_some());
+    }
+
+    #[test]
+    fn cancelled_batch_requests_leave_the_round() {
+        let context = context(PreemptionRule::Never);
+        let mut policy = PolicyKind::Batch.build();
+        policy.request(&context, &vehicle(1, DOWN_STRAIGHT, 100.0, SPEED), Duration::ZERO).unwrap_err();
+        assert!(!policy.cancel(1));
+        policy.tick(&context, Duration::ZERO);
+        assert!(policy.query(1).is_none());
+    }
+
+    #[test]
+    fn signal_phases_follow_the_approaches() {
+        let phase = SIGNAL_GREEN + SIGNAL_ALL_RED;
+        assert_eq!(SignalPolicy::green(Duration::ZERO), Some((APPROACHES[0], SIGNAL_GREEN)));
+        assert_eq!(SignalPolicy::green(secs(3.0)), Some((APPROACHES[0], SIGNAL_GREEN)));
+        assert_eq!(SignalPolicy::green(SIGNAL_GREEN), None);
+        assert_eq!(SignalPolicy::green(phase - secs(0.01)), None);
+        for (index, &approach) in APPROACHES.iter().enumerate() {
+            let start = phase * index as u32;
+            assert_eq!(SignalPolicy::green(start), Some((approach, start + SIGNAL_GREEN)));
+        }
+        let cycle = phase * APPROACHES.len() as u32;
+        assert_eq!(SignalPolicy::green(cycle + secs(1.0)), Some((APPROACHES[0], cycle + SIGNAL_GREEN)));
+    }
+
+    #[test]
+    fn signal_lets_in_vehicles_that_reach_the_box_while_green() {
+        let context = context(PreemptionRule::Never);
+        let mut policy = PolicyKind::Signal.build();
+        let (green, _) = SignalPolicy::green(Duration::ZERO).unwrap();
+        let red = APPROACHES[1];
+
+        let on_green = vehicle(1, (green, TurnDirection::Straight), 100.0, SPEED);
+        assert_eq!(policy.request(&context, &on_green, Duration::ZERO), Ok(()));
+        let on_red = vehicle(2, (red, TurnDirection::Straight), 100.0, SPEED);
+        assert_eq!(policy.request(&context, &on_red, Duration::ZERO), Err("Red light"));
+        // Would only reach the box after the green ends
+        let too_late = vehicle(3, (green, TurnDirection::Left), 100.0, SPEED);
+        assert_eq!(policy.request(&context, &too_late, SIGNAL_GREEN - secs(0.1)), Err("Red light"));
+        // Nobody gets in during the all-red clearance
+        assert_eq!(policy.request(&context, &on_green, SIGNAL_GREEN + secs(0.1)), Err("Red light"));
+    }
+
+    #[test]
+    fn all_way_stop_counts_the_stop_from_the_line() {
+        let context = context(PreemptionRule::Never);
+        let mut policy = PolicyKind::AllWayStop.build();
+
+        // Still rolling towards the line, or stopped short of it
+        let rolling = vehicle(1, DOWN_STRAIGHT, STOP_LINE_SETBACK, SPEED);
+        assert_eq!(policy.request(&context, &rolling, Duration::ZERO), Err("Stopping"));
+        let short = vehicle(1, DOWN_STRAIGHT, STOP_LINE_SETBACK + 3.0 * STOP_LINE_TOLERANCE, 0.0);
+        assert_eq!(policy.request(&context, &short, secs(1.0)), Err("Stopping"));
+        assert_eq!(policy.request(&context, &short, secs(5.0)), Err("Stopping"));
+
+        // The stop counts from the moment the vehicle stands at the line
+        let at_line = vehicle(1, DOWN_STRAIGHT, STOP_LINE_SETBACK, 0.0);
+        assert_eq!(policy.request(&context, &at_line, secs(6.0)), Err("Stopping"));
+        assert_eq!(policy.request(&context, &at_line, secs(6.0) + STOP_DURATION - secs(0.1)), Err("Stopping"));
+        assert_eq!(policy.request(&context, &at_line, secs(6.0) + STOP_DURATION), Ok(()));
+        assert!(policy.query(1).is_some());
+    }
+
+    #[test]
+    fn all_way_stop_yields_to_earlier_arrivals() {
+        let context = context(PreemptionRule::Never);
+        let mut policy = PolicyKind::AllWayStop.build();
+        let first = vehicle(1, DOWN_STRAIGHT, STOP_LINE_SETBACK, 0.0);
+        let crossing = vehicle(2, RIGHT_STRAIGHT, STOP_LINE_SETBACK, 0.0);
+        let opposite = vehicle(3, UP_STRAIGHT, STOP_LINE_SETBACK, 0.0);
+
+        policy.request(&context, &first, Duration::ZERO).unwrap_err();
+        policy.request(&context, &crossing, secs(0.1)).unwrap_err();
+        policy.request(&context, &opposite, secs(0.1)).unwrap_err();
+
+        // The first arrival is still standing, so the crossing vehicle waits for it
+        // while the opposite one, which never meets it, may go
+        let after_stop = secs(0.1) + STOP_DURATION;
+        assert_eq!(policy.request(&context, &crossing, after_stop), Err("Yielding to an earlier arrival"));
+        assert_eq!(policy.request(&context, &opposite, after_stop), Ok(()));
+        assert_eq!(policy.request(&context, &first, after_stop), Ok(()));
+        // The box is taken by the first arrival's crossing
+        assert_eq!(policy.request(&context, &crossing, after_stop), Err("Reservation conflict"));
+    }
+
+    #[test]
+    fn all_way_stop_forgets_vehicles_that_stop_asking() {
+        let context = context(PreemptionRule::Never);
+        let mut policy = PolicyKind::AllWayStop.build();
+        let first = vehicle(1, DOWN_STRAIGHT, STOP_LINE_SETBACK, 0.0);
+        let crossing = vehicle(2, RIGHT_STRAIGHT, STOP_LINE_SETBACK, 0.0);
+        policy.request(&context, &first, Duration::ZERO).unwrap_err();
+        policy.request(&context, &crossing, secs(0.1)).unwrap_err();
+
+        let after_stop = secs(0.1) + STOP_DURATION;
+        assert_eq!(policy.request(&context, &crossing, after_stop), Err("Yielding to an earlier arrival"));
+
+        // The first vehicle left the queue without going, e.g. it was removed
+        let later = STOP_QUEUE_TIMEOUT + secs(0.2);
+        policy.tick(&context, later);
+        assert_eq!(policy.request(&context, &crossing, later), Ok(()));
+    }
+
+    #[test]
+    fn all_way_stop_asks_from_the_stop_line() {
+        assert_eq!(PolicyKind::AllWayStop.build().request_distance(), STOP_LINE_SETBACK + STOP_LINE_TOLERANCE);
+        assert_eq!(PolicyKind::Fcfs.build().request_distance(), RESERVATION_DISTANCE);
+    }
+}
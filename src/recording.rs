@@ -0,0 +1,207 @@
+use crate::scenario::Scenario;
+use crate::simulation::AUTO_SPAWN_INTERVAL;
+use crate::MovementDirection;
+use crate::Simulation;
+use crate::TurnDirection;
+use serde::{Deserialize, Serialize};
+
+// Outside input to a running simulation. Everything else a run does follows
+// from the scenario and the seed, so these are all a recording has to keep.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum InputEvent {
+    Spawn { approach: MovementDirection, turn: TurnDirection },
+    // Spawn on the approach with a turn drawn from the simulation RNG
+    SpawnRandomTurn { approach: MovementDirection },
+    SpawnRandom,
+    ToggleAutoSpawn,
+}
+
+impl InputEvent {
+    pub fn apply(&self, simulation: &mut Simulation) {
+        match *self {
+            InputEvent::Spawn { approach, turn } => {
+                simulation.spawn(approach, turn);
+            }
+            InputEvent::SpawnRandomTurn { approach } => {
+                simulation.spawn_random_turn(approach);
+            }
+            InputEvent::SpawnRandom => {
+                simulation.spawn_random();
+            }
+            InputEvent::ToggleAutoSpawn => {
+                if simulation.auto_spawn_interval().is_some() {
+                    simulation.set_auto_spawn(None);
+                } else {
+                    simulation.set_auto_spawn(Some(AUTO_SPAWN_INTERVAL));
+                }
+            }
+        }
+    }
+}
+
+// Input event applied after `tick` ticks had run
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct RecordedEvent {
+    pub tick: u64,
+    pub event: InputEvent,
+}
+
+// Everything needed to re-run a simulation tick for tick: the seed, the scenario
+// in file form and the input events with the tick they arrived at
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Recording {
+    pub seed: u64,
+    pub scenario: serde_json::Value,
+    pub events: Vec<RecordedEvent>,
+    // Ticks the recorded run lasted
+    pub end_tick: u64,
+}
+
+impl Recording {
+    pub fn load(path: &str) -> Result<Self, String> {
+        let text = std::fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+        serde_json::from_str(&text).map_err(|e| format!("{}: {}", path, e))
+    }
+
+    pub fn save(&self, path: &str) -> Result<(), String> {
+        let text = serde_json::to_string_pretty(self).expect("recordings are always serializable");
+        std::fs::write(path, text + "\n").map_err(|e| format!("{}: {}", path, e))
+    }
+
+    pub fn scenario(&self) -> Result<Scenario, String> {
+        Scenario::from_json(&self.scenario.to_string()).map_err(|e| format!("recorded scenario: {}", e))
+    }
+
+    // A fresh simulation in the state the recorded run started from, and the
+    // replayer feeding it the recorded input
+    pub fn start(&self) -> Result<(Simulation, Replayer), String> {
+        let simulation = self.scenario()?.build(self.seed)?;
+        let mut events = self.events.clone();
+        events.sort_by_key(|recorded| recorded.tick);
+        Ok((simulation, Replayer { events, next: 0, end_tick: self.end_tick }))
+    }
+}
+
+// Applies input events to a simulation while keeping a log of them
+pub struct Recorder {
+    seed: u64,
+    scenario: serde_json::Value,
+    events: Vec<RecordedEvent>,
+}
+
+impl Recorder {
+    // `seed` is the one the simulation was actually built with
+    pub fn new(scenario: &Scenario, seed: u64) -> Self {
+        let scenario = serde_json::from_str(&scenario.to_json()).expect("scenario JSON is valid");
+        Recorder { seed, scenario, events: Vec::new() }
+    }
+
+    pub fn apply(&mut self, event: InputEvent, simulation: &mut Simulation) {
+        self.events.push(RecordedEvent { tick: simulation.ticks(), event });
+        event.apply(simulation);
+    }
+
+    pub fn finish(self, simulation: &Simulation) -> Recording {
+        Recording { seed: self.seed, scenario: self.scenario, events: self.events, end_tick: simulation.ticks() }
+    }
+}
+
+// Feeds recorded input into a simulation at the ticks it originally arrived at
+pub struct Replayer {
+    events: Vec<RecordedEvent>,
+    next: usize,
+    end_tick: u64,
+}
+
+impl Replayer {
+    // Apply the events due before the next tick, then run it
+    pub fn step(&mut self, simulation: &mut Simulation) {
+        while let Some(recorded) = self.events.get(self.next).filter(|recorded| recorded.tick <= simulation.ticks()) {
+            recorded.event.apply(simulation);
+            self.next += 1;
+        }
+        simulation.step();
+    }
+
+    // True once the simulation ran as long as the recorded one
+    pub fn is_finished(&self, simulation: &Simulation) -> bool {
+        simulation.ticks() >= self.end_tick || simulation.is_halted()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::trace::{TraceField, TraceFormat, TraceWriter, Tracer};
+    use std::cell::RefCell;
+    use std::io::Write;
+    use std::rc::Rc;
+    use std::time::Duration;
+
+    // Trace output that stays readable after the simulation took the writer
+    #[derive(Clone, Default)]
+    struct Shared(Rc<RefCell<Vec<u8>>>);
+
+    impl Write for Shared {
+        fn write(&mut self, bytes: &[u8]) -> std::io::Result<usize> {
+            self.0.borrow_mut().write(bytes)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn trace(simulation: &mut Simulation) -> Shared {
+        let out = Shared::default();
+        let writer = TraceWriter::new(out.clone(), TraceFormat::Csv, TraceField::ALL.to_vec());
+        simulation.set_tracer(Some(Tracer::new(Box::new(writer), Duration::ZERO)));
+        out
+    }
+
+    #[test]
+    fn replay_repeats_the_recorded_run() {
+        let scenario = Scenario::from_json(
+            r#"{ "duration": 30, "demand": [{ "approach": "left", "turn": "straight", "rate": 0.2 }] }"#,
+        )
+        .unwrap();
+        let mut simulation = scenario.build(7).unwrap();
+        let recorded_trace = trace(&mut simulation);
+        let mut recorder = Recorder::new(&scenario, 7);
+        let inputs = [
+            (0, InputEvent::Spawn { approach: MovementDirection::Up, turn: TurnDirection::Left }),
+            (20, InputEvent::SpawnRandomTurn { approach: MovementDirection::Right }),
+            (20, InputEvent::SpawnRandom),
+            (100, InputEvent::ToggleAutoSpawn),
+            (300, InputEvent::ToggleAutoSpawn),
+            (450, InputEvent::SpawnRandom),
+        ];
+        for tick in 0..600 {
+            for &(_, event) in inputs.iter().filter(|&&(at, _)| at == tick) {
+                recorder.apply(event, &mut simulation);
+            }
+            simulation.step();
+        }
+        simulation.finish_trace().unwrap();
+
+        let path = std::env::temp_dir().join(format!("recording_{}.json", std::process::id()));
+        let path = path.to_str().unwrap();
+        recorder.finish(&simulation).save(path).unwrap();
+        let recording = Recording::load(path).unwrap();
+        std::fs::remove_file(path).unwrap();
+        assert_eq!(recording.events.len(), inputs.len());
+
+        let (mut replay, mut replayer) = recording.start().unwrap();
+        let replayed_trace = trace(&mut replay);
+        while !replayer.is_finished(&replay) {
+            replayer.step(&mut replay);
+        }
+        replay.finish_trace().unwrap();
+
+        assert_eq!(replay.ticks(), simulation.ticks());
+        assert_eq!(replay.statistics().to_json(), simulation.statistics().to_json());
+        assert!(!recorded_trace.0.borrow().is_empty());
+        assert_eq!(*replayed_trace.0.borrow(), *recorded_trace.0.borrow());
+    }
+}
@@ -0,0 +1,51 @@
+use crate::TurnDirection;
+
+// Turns a vehicle takes at the successive intersections on its way through the network.
+// The vehicle is always at, or approaching, the intersection of `current()`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Route {
+    turns: Vec<TurnDirection>,
+    index: usize,
+}
+
+impl Route {
+    pub fn new(turns: Vec<TurnDirection>) -> Self {
+        assert!(!turns.is_empty(), "a route needs at least one turn");
+        Route { turns, index: 0 }
+    }
+
+    // Route crossing a single intersection
+    pub fn single(turn_direction: TurnDirection) -> Self {
+        Route::new(vec![turn_direction])
+    }
+
+    pub fn turns(&self) -> &[TurnDirection] {
+        &self.turns
+    }
+
+    // Number of intersections already left behind
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    // Turn at the current intersection
+    pub fn current(&self) -> TurnDirection {
+        self.turns[self.index]
+    }
+
+    // Turn at the intersection after the current one, if the route goes on
+    pub fn next(&self) -> Option<TurnDirection> {
+        self.turns.get(self.index + 1).copied()
+    }
+
+    // Move on to the next intersection; stays on the last turn once the route is done
+    pub fn advance(&mut self) {
+        if self.index + 1 < self.turns.len() {
+            self.index += 1;
+        }
+    }
+
+    pub fn is_last(&self) -> bool {
+        self.index + 1 == self.turns.len()
+    }
+}
@@ -0,0 +1,719 @@
+use crate::collision::CollisionDetector;
+use crate::conflict_matrix::{movement_name, parse_movement, Movement};
+use crate::demand::{parse_movement_direction, parse_turn_direction, ArrivalRate, DemandProfile, RatePeriod};
+use crate::geometry::Geometry;
+use crate::intersection_manager::PreemptionRule;
+use crate::network::{RoadNetwork, DEFAULT_GRID_SPACING};
+use crate::physics_engine::PhysicsEngine;
+use crate::policy::PolicyKind;
+use crate::simulation::DEFAULT_TIMESTEP;
+use crate::tiles::TileConfig;
+use crate::vehicle::VehicleType;
+use crate::Position;
+use crate::Simulation;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::path::Path;
+use std::time::Duration;
+
+// Everything needed to set up a reproducible run. Scenario files are JSON; every
+// key is optional and falls back to the values of `Scenario::default()`:
+//
+// {
+//   "seed": 42,
+//   "duration": 120,
+//   "timestep": 0.0166,
+//   "geometry": { "center": [504, 504], "lane_width": 56, "lanes_per_approach": 3,
+//                 "world_width": 1000, "world_height": 1000, "spawn_margin": 67 },
+//   "grid": { "rows": 2, "cols": 2, "spacing": 600 },
+//   "physics": { "safety_distance": 5, "max_velocity": 3000 },
+//   "collisions": { "close_call_fraction": 0.5, "halt_on_collision": false },
+//   "demand": [
+//     { "approach": "up", "turn": "left", "rate": 0.2 },
+//     { "approach": "left", "turn": "straight",
+//       "periods": [{ "start": 0, "end": 60, "count": 30 }] }
+//   ],
+//   "auto_spawn_interval": 0.5,
+//   "vehicle_types": [{ "name": "car", "size": 55, "velocity": 600, "weight": 1, "priority": 0 }],
+//   "policy": "fcfs",
+//   "preemption": "never",
+//   "tiles": { "per_side": 12, "slot": 0.05, "buffer": 5, "time_margin": 0.1 },
+//   "conflicts": [{ "between": ["up right", "down right"], "conflict": true }]
+// }
+//
+// Times are in seconds. `conflicts` overrides entries of the conflict matrix
+// derived from the geometry, see `ConflictMatrix`. Instead of `demand` a `demand_csv` file in the format read
+// by `DemandProfile::from_csv` can be given, relative to the scenario file.
+#[derive(Debug, Clone)]
+pub struct Scenario {
+    // None draws a fresh seed for every run
+    pub seed: Option<u64>,
+    // None runs until stopped by hand
+    pub duration: Option<Duration>,
+    pub timestep: Duration,
+    pub geometry: Geometry,
+    pub grid_rows: usize,
+    pub grid_cols: usize,
+    pub grid_spacing: f32,
+    pub physics_engine: PhysicsEngine,
+    pub close_call_fraction: f32,
+    pub halt_on_collision: bool,
+    pub demand: Option<DemandProfile>,
+    pub auto_spawn_interval: Option<Duration>,
+    pub vehicle_types: Vec<VehicleType>,
+    pub policy: PolicyKind,
+    pub preemption_rule: PreemptionRule,
+    // Space-time resolution of the reservations at every intersection
+    pub tile_config: TileConfig,
+    // Conflict matrix entries set by hand, applied in order
+    pub conflict_overrides: Vec<(Movement, Movement, bool)>,
+}
+
+impl Default for Scenario {
+    fn default() -> Self {
+        Scenario {
+            seed: None,
+            duration: None,
+            timestep: DEFAULT_TIMESTEP,
+            geometry: Geometry::default(),
+            grid_rows: 1,
+            grid_cols: 1,
+            grid_spacing: DEFAULT_GRID_SPACING,
+            physics_engine: PhysicsEngine::new(5.0, 3000.0), // Safety distance of 5 units
+            close_call_fraction: 0.5,
+            halt_on_collision: false,
+            demand: None,
+            auto_spawn_interval: None,
+            vehicle_types: vec![VehicleType::default()],
+            policy: PolicyKind::Fcfs,
+            preemption_rule: PreemptionRule::Never,
+            tile_config: TileConfig::default(),
+            conflict_overrides: Vec::new(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawScenario {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    seed: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    duration: Option<f64>,
+    timestep: Option<f64>,
+    geometry: Option<RawGeometry>,
+    grid: Option<RawGrid>,
+    physics: Option<RawPhysics>,
+    collisions: Option<RawCollisions>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    demand: Option<Vec<RawDemand>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    demand_csv: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    auto_spawn_interval: Option<f64>,
+    vehicle_types: Option<Vec<RawVehicleType>>,
+    policy: Option<String>,
+    preemption: Option<String>,
+    tiles: Option<RawTiles>,
+    conflicts: Option<Vec<RawConflict>>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawGeometry {
+    center: Option<[f32; 2]>,
+    lane_width: Option<f32>,
+    lanes_per_approach: Option<usize>,
+    world_width: Option<f32>,
+    world_height: Option<f32>,
+    spawn_margin: Option<f32>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawGrid {
+    rows: usize,
+    cols: usize,
+    spacing: Option<f32>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawPhysics {
+    safety_distance: Option<f32>,
+    max_velocity: Option<f32>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawCollisions {
+    close_call_fraction: Option<f32>,
+    halt_on_collision: Option<bool>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawTiles {
+    per_side: Option<usize>,
+    slot: Option<f64>,
+    buffer: Option<f32>,
+    time_margin: Option<f64>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawConflict {
+    between: [String; 2],
+    conflict: bool,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawDemand {
+    approach: String,
+    turn: String,
+    // Constant rate in vehicles per second, or a time-of-day curve
+    #[serde(skip_serializing_if = "Option::is_none")]
+    rate: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    periods: Option<Vec<RawPeriod>>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawPeriod {
+    start: f64,
+    end: f64,
+    // Vehicles arriving over the whole period, or the rate in vehicles per second
+    #[serde(skip_serializing_if = "Option::is_none")]
+    count: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    rate: Option<f32>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawVehicleType {
+    name: String,
+    size: Option<f32>,
+    velocity: Option<f32>,
+    weight: Option<f32>,
+    priority: Option<u32>,
+}
+
+impl Scenario {
+    pub fn load(path: &str) -> Result<Self, String> {
+        let text = std::fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+        let base_dir = Path::new(path).parent().unwrap_or(Path::new(""));
+        Self::parse(&text, base_dir).map_err(|e| format!("{}: {}", path, e))
+    }
+
+    // Parse a scenario; a `demand_csv` path is taken relative to the working directory
+    pub fn from_json(text: &str) -> Result<Self, String> {
+        Self::parse(text, Path::new(""))
+    }
+
+    fn parse(text: &str, base_dir: &Path) -> Result<Self, String> {
+        let raw: RawScenario = serde_json::from_str(text).map_err(|e| e.to_string())?;
+        let mut scenario = Scenario { seed: raw.seed, ..Scenario::default() };
+
+        if let Some(duration) = raw.duration {
+            scenario.duration = Some(seconds(duration, "duration")?);
+        }
+        if let Some(timestep) = raw.timestep {
+            scenario.timestep = seconds(timestep, "timestep")?;
+        }
+
+        if let Some(geometry) = raw.geometry {
+            let defaults = Geometry::default();
+            let center = geometry.center.map_or(defaults.center, |[x, y]| Position::new(x, y));
+            scenario.geometry = Geometry {
+                center,
+                lane_width: geometry.lane_width.unwrap_or(defaults.lane_width),
+                lanes_per_approach: geometry.lanes_per_approach.unwrap_or(defaults.lanes_per_approach),
+                world_width: geometry.world_width.unwrap_or(defaults.world_width),
+                world_height: geometry.world_height.unwrap_or(defaults.world_height),
+                spawn_margin: geometry.spawn_margin.unwrap_or(defaults.spawn_margin),
+            };
+            if scenario.geometry.spawn_margin.is_nan() || scenario.geometry.spawn_margin < 0.0 {
+                return Err(format!("geometry.spawn_margin must not be negative, got {}", scenario.geometry.spawn_margin));
+            }
+        }
+        scenario.geometry.validate().map_err(|e| format!("geometry: {}", e))?;
+
+        if let Some(grid) = raw.grid {
+            scenario.grid_rows = grid.rows;
+            scenario.grid_cols = grid.cols;
+            scenario.grid_spacing = grid.spacing.unwrap_or(DEFAULT_GRID_SPACING);
+        }
+        scenario.network().map_err(|e| format!("grid: {}", e))?;
+
+        if let Some(physics) = raw.physics {
+            let safety_distance = physics.safety_distance.unwrap_or(scenario.physics_engine.safety_distance());
+            let max_velocity = physics.max_velocity.unwrap_or(scenario.physics_engine.max_velocity());
+            if safety_distance.is_nan() || safety_distance < 0.0 {
+                return Err(format!("physics.safety_distance must not be negative, got {}", safety_distance));
+            }
+            positive(max_velocity, "physics.max_velocity")?;
+            scenario.physics_engine = PhysicsEngine::new(safety_distance, max_velocity);
+        }
+
+        if let Some(collisions) = raw.collisions {
+            if let Some(fraction) = collisions.close_call_fraction {
+                if !(0.0..=1.0).contains(&fraction) {
+                    return Err(format!("collisions.close_call_fraction must be between 0 and 1, got {}", fraction));
+                }
+                scenario.close_call_fraction = fraction;
+            }
+            scenario.halt_on_collision = collisions.halt_on_collision.unwrap_or(false);
+        }
+
+        match (raw.demand, raw.demand_csv) {
+            (Some(_), Some(_)) => return Err("demand and demand_csv cannot both be given".to_string()),
+            (Some(entries), None) => scenario.demand = Some(parse_demand(entries)?),
+            (None, Some(path)) => {
+                let path = base_dir.join(path);
+                let demand = DemandProfile::load_csv(&path.to_string_lossy()).map_err(|e| format!("demand_csv: {}", e))?;
+                scenario.demand = Some(demand);
+            }
+            (None, None) => {}
+        }
+
+        if let Some(interval) = raw.auto_spawn_interval {
+            scenario.auto_spawn_interval = Some(seconds(interval, "auto_spawn_interval")?);
+        }
+
+        let vehicle_types_listed = raw.vehicle_types.is_some();
+        if let Some(vehicle_types) = raw.vehicle_types {
+            scenario.vehicle_types = parse_vehicle_types(vehicle_types)?;
+        }
+
+        if let Some(policy) = raw.policy {
+            scenario.policy = PolicyKind::from_name(&policy).map_err(|e| format!("policy: {}", e))?;
+        }
+
+        if let Some(preemption) = raw.preemption {
+            scenario.preemption_rule = PreemptionRule::parse(&preemption).ok_or_else(|| {
+                let known: Vec<&str> = PreemptionRule::ALL.iter().map(PreemptionRule::name).collect();
+                format!("preemption: unknown rule '{}', expected one of {}", preemption, known.join(", "))
+            })?;
+        }
+
+        if let Some(tiles) = raw.tiles {
+            let defaults = TileConfig::default();
+            scenario.tile_config = TileConfig {
+                tiles_per_side: tiles.per_side.unwrap_or(defaults.tiles_per_side),
+                slot: tiles.slot.map_or(Ok(defaults.slot), |slot| seconds(slot, "tiles.slot"))?,
+                buffer: tiles.buffer.unwrap_or(defaults.buffer),
+                time_margin: tiles
+                    .time_margin
+                    .map_or(Ok(defaults.time_margin), |margin| non_negative_seconds(margin, "tiles.time_margin"))?,
+            };
+            scenario.tile_config.validate().map_err(|e| format!("tiles: {}", e))?;
+        }
+
+        if let Some(conflicts) = raw.conflicts {
+            for (index, entry) in conflicts.into_iter().enumerate() {
+                let [first, second] = entry.between;
+                let movement = |name: String| {
+                    parse_movement(&name).ok_or_else(|| {
+                        format!("conflicts[{}].between: unknown movement '{}', expected '<approach> <turn>'", index, name)
+                    })
+                };
+                scenario.conflict_overrides.push((movement(first)?, movement(second)?, entry.conflict));
+            }
+        }
+
+        // The default vehicle type has to fit the geometry and physics as well
+        check_vehicle_types(&scenario, vehicle_types_listed)?;
+
+        Ok(scenario)
+    }
+
+    // The scenario in file form with every value spelled out and the demand inlined,
+    // so it reproduces the same setup without the files it was loaded from
+    pub fn to_json(&self) -> String {
+        let demand = self.demand.as_ref().map(|demand| {
+            demand
+                .rates()
+                .map(|(movement_direction, turn_direction, rate)| {
+                    let (rate, periods) = match rate {
+                        ArrivalRate::Constant(rate) => (Some(*rate), None),
+                        ArrivalRate::Piecewise(periods) => (
+                            None,
+                            Some(
+                                periods
+                                    .iter()
+                                    .map(|period| RawPeriod {
+                                        start: period.start.as_secs_f64(),
+                                        end: period.end.as_secs_f64(),
+                                        count: None,
+                                        rate: Some(period.rate),
+                                    })
+                                    .collect(),
+                            ),
+                        ),
+                    };
+                    RawDemand {
+                        approach: format!("{:?}", movement_direction).to_lowercase(),
+                        turn: format!("{:?}", turn_direction).to_lowercase(),
+                        rate,
+                        periods,
+                    }
+                })
+                .collect()
+        });
+
+        let raw = RawScenario {
+            seed: self.seed,
+            duration: self.duration.map(|duration| duration.as_secs_f64()),
+            timestep: Some(self.timestep.as_secs_f64()),
+            geometry: Some(RawGeometry {
+                center: Some([self.geometry.center.x, self.geometry.center.y]),
+                lane_width: Some(self.geometry.lane_width),
+                lanes_per_approach: Some(self.geometry.lanes_per_approach),
+                world_width: Some(self.geometry.world_width),
+                world_height: Some(self.geometry.world_height),
+                spawn_margin: Some(self.geometry.spawn_margin),
+            }),
+            grid: Some(RawGrid { rows: self.grid_rows, cols: self.grid_cols, spacing: Some(self.grid_spacing) }),
+            physics: Some(RawPhysics {
+                safety_distance: Some(self.physics_engine.safety_distance()),
+                max_velocity: Some(self.physics_engine.max_velocity()),
+            }),
+            collisions: Some(RawCollisions {
+                close_call_fraction: Some(self.close_call_fraction),
+                halt_on_collision: Some(self.halt_on_collision),
+            }),
+            demand,
+            demand_csv: None,
+            auto_spawn_interval: self.auto_spawn_interval.map(|interval| interval.as_secs_f64()),
+            vehicle_types: Some(
+                self.vehicle_types
+                    .iter()
+                    .map(|vehicle_type| RawVehicleType {
+                        name: vehicle_type.name.clone(),
+                        size: Some(vehicle_type.size),
+                        velocity: Some(vehicle_type.velocity),
+                        weight: Some(vehicle_type.weight),
+                        priority: Some(vehicle_type.priority),
+                    })
+                    .collect(),
+            ),
+            policy: Some(self.policy.name().to_string()),
+            preemption: Some(self.preemption_rule.name().to_string()),
+            tiles: Some(RawTiles {
+                per_side: Some(self.tile_config.tiles_per_side),
+                slot: Some(self.tile_config.slot.as_secs_f64()),
+                buffer: Some(self.tile_config.buffer),
+                time_margin: Some(self.tile_config.time_margin.as_secs_f64()),
+            }),
+            conflicts: Some(
+                self.conflict_overrides
+                    .iter()
+                    .map(|&(first, second, conflict)| RawConflict {
+                        between: [movement_name(first), movement_name(second)],
+                        conflict,
+                    })
+                    .collect(),
+            ),
+        };
+        serde_json::to_string_pretty(&raw).expect("scenarios are always serializable")
+    }
+
+    pub fn network(&self) -> Result<RoadNetwork, String> {
+        let mut network = RoadNetwork::grid(self.geometry.clone(), self.grid_rows, self.grid_cols, self.grid_spacing)?;
+        for node in 0..network.nodes().len() {
+            let manager = network.manager_mut(node);
+            manager.set_policy(self.policy);
+            manager.set_tile_config(self.tile_config);
+            manager.set_preemption_rule(self.preemption_rule);
+            let mut conflict_matrix = manager.conflict_matrix().clone();
+            for &(first, second, conflict) in &self.conflict_overrides {
+                conflict_matrix.set(first, second, conflict);
+            }
+            manager.set_conflict_matrix(conflict_matrix);
+        }
+        Ok(network)
+    }
+
+    // Set up a simulation for this scenario. `seed` is used when the scenario does
+    // not fix one; command-line overrides are expected to be applied beforehand.
+    pub fn build(&self, seed: u64) -> Result<Simulation, String> {
+        let mut simulation = Simulation::new(self.physics_engine, self.timestep, self.seed.unwrap_or(seed));
+        simulation.set_network(self.network()?);
+        simulation.set_collision_detector(CollisionDetector::new(self.close_call_fraction, self.halt_on_collision));
+        simulation.set_demand(self.demand.clone());
+        simulation.set_auto_spawn(self.auto_spawn_interval);
+        simulation.set_vehicle_types(self.vehicle_types.clone());
+        Ok(simulation)
+    }
+}
+
+fn parse_demand(entries: Vec<RawDemand>) -> Result<DemandProfile, String> {
+    let mut profile = DemandProfile::new();
+    let mut seen = HashSet::new();
+    for (index, entry) in entries.into_iter().enumerate() {
+        let path = format!("demand[{}]", index);
+        let movement_direction = parse_movement_direction(&entry.approach)
+            .ok_or_else(|| format!("{}.approach: unknown approach '{}'", path, entry.approach))?;
+        let turn_direction = parse_turn_direction(&entry.turn)
+            .ok_or_else(|| format!("{}.turn: unknown turn '{}'", path, entry.turn))?;
+        if !seen.insert((movement_direction, turn_direction)) {
+            return Err(format!("{}: {} {} is already given by an earlier entry", path, entry.approach, entry.turn));
+        }
+
+        match (entry.rate, entry.periods) {
+            (Some(rate), None) => {
+                if rate.is_nan() || rate < 0.0 {
+                    return Err(format!("{}.rate must not be negative, got {}", path, rate));
+                }
+                profile.set_rate(movement_direction, turn_direction, ArrivalRate::Constant(rate));
+            }
+            (None, Some(raw_periods)) => {
+                let mut periods = Vec::new();
+                for (period_index, period) in raw_periods.into_iter().enumerate() {
+                    let period_path = format!("{}.periods[{}]", path, period_index);
+                    let start = non_negative_seconds(period.start, &format!("{}.start", period_path))?;
+                    let end = non_negative_seconds(period.end, &format!("{}.end", period_path))?;
+                    if end <= start {
+                        return Err(format!("{}: end must be after start", period_path));
+                    }
+                    let rate = match (period.count, period.rate) {
+                        (Some(count), None) => count as f32 / (end - start).as_secs_f32(),
+                        (None, Some(rate)) if rate.is_nan() || rate < 0.0 => {
+                            return Err(format!("{}.rate must not be negative, got {}", period_path, rate));
+                        }
+                        (None, Some(rate)) => rate,
+                        _ => return Err(format!("{}: exactly one of count and rate must be given", period_path)),
+                    };
+                    periods.push(RatePeriod { start, end, rate });
+                }
+                profile
+                    .set_periods(movement_direction, turn_direction, periods)
+                    .map_err(|e| format!("{}.periods: {}", path, e))?;
+            }
+            _ => return Err(format!("{}: exactly one of rate and periods must be given", path)),
+        }
+    }
+    Ok(profile)
+}
+
+fn parse_vehicle_types(entries: Vec<RawVehicleType>) -> Result<Vec<VehicleType>, String> {
+    if entries.is_empty() {
+        return Err("vehicle_types must not be empty".to_string());
+    }
+    let defaults = VehicleType::default();
+    let mut names = HashSet::new();
+    let mut vehicle_types = Vec::new();
+    for (index, entry) in entries.into_iter().enumerate() {
+        let path = format!("vehicle_types[{}]", index);
+        if !names.insert(entry.name.clone()) {
+            return Err(format!("{}.name: duplicate vehicle type '{}'", path, entry.name));
+        }
+        let vehicle_type = VehicleType {
+            size: positive(entry.size.unwrap_or(defaults.size), &format!("{}.size", path))?,
+            velocity: positive(entry.velocity.unwrap_or(defaults.velocity), &format!("{}.velocity", path))?,
+            weight: positive(entry.weight.unwrap_or(defaults.weight), &format!("{}.weight", path))?,
+            priority: entry.priority.unwrap_or(defaults.priority),
+            name: entry.name,
+        };
+        vehicle_types.push(vehicle_type);
+    }
+    Ok(vehicle_types)
+}
+
+// Every vehicle type has to fit into a lane and stay below the speed limit. `listed`
+// tells whether the types come from the file or are the defaults.
+fn check_vehicle_types(scenario: &Scenario, listed: bool) -> Result<(), String> {
+    for (index, vehicle_type) in scenario.vehicle_types.iter().enumerate() {
+        let prefix = if listed {
+            format!("vehicle_types[{}].", index)
+        } else {
+            format!("default vehicle type '{}': ", vehicle_type.name)
+        };
+        if vehicle_type.size > scenario.geometry.lane_width {
+            return Err(format!(
+                "{}size {} does not fit into a lane of width {}",
+                prefix, vehicle_type.size, scenario.geometry.lane_width
+            ));
+        }
+        if vehicle_type.velocity > scenario.physics_engine.max_velocity() {
+            return Err(format!(
+                "{}velocity {} exceeds physics.max_velocity {}",
+                prefix,
+                vehicle_type.velocity,
+                scenario.physics_engine.max_velocity()
+            ));
+        }
+    }
+    Ok(())
+}
+
+fn positive(value: f32, path: &str) -> Result<f32, String> {
+    if value.is_nan() || value <= 0.0 {
+        return Err(format!("{} must be positive, got {}", path, value));
+    }
+    Ok(value)
+}
+
+fn seconds(value: f64, path: &str) -> Result<Duration, String> {
+    if value.is_nan() || value <= 0.0 {
+        return Err(format!("{} must be positive, got {}", path, value));
+    }
+    Duration::try_from_secs_f64(value).map_err(|_| format!("{} is out of range, got {}", path, value))
+}
+
+fn non_negative_seconds(value: f64, path: &str) -> Result<Duration, String> {
+    Duration::try_from_secs_f64(value).map_err(|_| format!("{} must be a non-negative time, got {}", path, value))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn error(text: &str) -> String {
+        Scenario::from_json(text).unwrap_err()
+    }
+
+    #[test]
+    fn empty_scenario_uses_the_defaults() {
+        let scenario = Scenario::from_json("{}").unwrap();
+        assert_eq!(scenario.seed, None);
+        assert_eq!(scenario.timestep, DEFAULT_TIMESTEP);
+        assert_eq!((scenario.grid_rows, scenario.grid_cols), (1, 1));
+        assert_eq!(scenario.policy, PolicyKind::Fcfs);
+        assert_eq!(scenario.vehicle_types, vec![VehicleType::default()]);
+    }
+
+    #[test]
+    fn unknown_keys_are_rejected() {
+        assert!(error(r#"{ "sead": 1 }"#).contains("unknown field `sead`"));
+        assert!(error(r#"{ "grid": { "rows": 2, "cols": 2, "gap": 600 } }"#).contains("unknown field `gap`"));
+        assert!(error(r#"{ "vehicle_types": [{ "name": "car", "speed": 600 }] }"#)
+            .contains("unknown field `speed`"));
+        assert!(error(r#"{ "demand": [{ "approach": "up", "turn": "left", "rate": 1, "count": 2 }] }"#)
+            .contains("unknown field `count`"));
+    }
+
+    #[test]
+    fn invalid_values_are_rejected() {
+        let cases = [
+            (r#"{ "duration": 0 }"#, "duration must be positive, got 0"),
+            (r#"{ "timestep": -0.1 }"#, "timestep must be positive, got -0.1"),
+            (r#"{ "geometry": { "spawn_margin": -1 } }"#, "geometry.spawn_margin must not be negative, got -1"),
+            (r#"{ "grid": { "rows": 0, "cols": 2 } }"#, "grid: grid needs at least one row and column, got 0x2"),
+            (r#"{ "physics": { "safety_distance": -5 } }"#, "physics.safety_distance must not be negative, got -5"),
+            (
+                r#"{ "collisions": { "close_call_fraction": 2 } }"#,
+                "collisions.close_call_fraction must be between 0 and 1, got 2",
+            ),
+            (r#"{ "vehicle_types": [] }"#, "vehicle_types must not be empty"),
+            (
+                r#"{ "vehicle_types": [{ "name": "car" }, { "name": "car" }] }"#,
+                "vehicle_types[1].name: duplicate vehicle type 'car'",
+            ),
+            (
+                r#"{ "vehicle_types": [{ "name": "car", "weight": 0 }] }"#,
+                "vehicle_types[0].weight must be positive, got 0",
+            ),
+            (
+                r#"{ "policy": "roundabout" }"#,
+                "policy: unknown policy 'roundabout', expected one of fcfs, whole_box, batch, signal, all_way_stop",
+            ),
+            (
+                r#"{ "conflicts": [{ "between": ["up right", "sideways"], "conflict": true }] }"#,
+                "conflicts[0].between: unknown movement 'sideways', expected '<approach> <turn>'",
+            ),
+        ];
+        for (text, message) in cases {
+            assert_eq!(error(text), message, "{}", text);
+        }
+    }
+
+    #[test]
+    fn invalid_demand_is_rejected() {
+        let cases = [
+            (
+                r#"{ "demand": [{ "approach": "north", "turn": "left", "rate": 1 }] }"#,
+                "demand[0].approach: unknown approach 'north'",
+            ),
+            (
+                r#"{ "demand": [{ "approach": "up", "turn": "left" }] }"#,
+                "demand[0]: exactly one of rate and periods must be given",
+            ),
+            (
+                r#"{ "demand": [{ "approach": "up", "turn": "left", "rate": 1 },
+                                { "approach": "Up", "turn": "left", "rate": 2 }] }"#,
+                "demand[1]: Up left is already given by an earlier entry",
+            ),
+            (
+                r#"{ "demand": [{ "approach": "up", "turn": "left",
+                                  "periods": [{ "start": 10, "end": 5, "count": 1 }] }] }"#,
+                "demand[0].periods[0]: end must be after start",
+            ),
+            (
+                r#"{ "demand": [{ "approach": "up", "turn": "left",
+                     "periods": [{ "start": 0, "end": 60, "count": 1 }, { "start": 30, "end": 90, "rate": 1 }] }] }"#,
+                "demand[0].periods: overlapping periods for Up Left starting at 0 s and 30 s",
+            ),
+            (r#"{ "demand": [], "demand_csv": "demand.csv" }"#, "demand and demand_csv cannot both be given"),
+        ];
+        for (text, message) in cases {
+            assert_eq!(error(text), message, "{}", text);
+        }
+    }
+
+    #[test]
+    fn vehicle_types_must_fit_geometry_and_physics() {
+        assert_eq!(
+            error(r#"{ "vehicle_types": [{ "name": "truck", "size": 80 }] }"#),
+            "vehicle_types[0].size 80 does not fit into a lane of width 56"
+        );
+        // The default vehicle type is checked even when the file lists none
+        assert_eq!(
+            error(r#"{ "physics": { "max_velocity": 100 } }"#),
+            "default vehicle type 'car': velocity 600 exceeds physics.max_velocity 100"
+        );
+        assert_eq!(
+            error(r#"{ "geometry": { "lane_width": 40 } }"#),
+            "default vehicle type 'car': size 55 does not fit into a lane of width 40"
+        );
+    }
+
+    #[test]
+    fn grid_spacing_defaults_when_not_given() {
+        let scenario = Scenario::from_json(r#"{ "grid": { "rows": 2, "cols": 3 } }"#).unwrap();
+        assert_eq!((scenario.grid_rows, scenario.grid_cols), (2, 3));
+        assert_eq!(scenario.grid_spacing, DEFAULT_GRID_SPACING);
+    }
+
+    #[test]
+    fn to_json_round_trips() {
+        let text = r#"{
+            "seed": 7,
+            "duration": 30,
+            "grid": { "rows": 1, "cols": 2, "spacing": 700 },
+            "demand": [{ "approach": "left", "turn": "right", "periods": [{ "start": 0, "end": 10, "count": 5 }] }],
+            "vehicle_types": [{ "name": "car", "weight": 3 }, { "name": "bus", "size": 50, "velocity": 400 }],
+            "policy": "batch",
+            "preemption": "higher_priority",
+            "conflicts": [{ "between": ["up right", "down right"], "conflict": true }]
+        }"#;
+        let scenario = Scenario::from_json(text).unwrap();
+        let reloaded = Scenario::from_json(&scenario.to_json()).unwrap();
+
+        assert_eq!(reloaded.seed, Some(7));
+        assert_eq!(reloaded.duration, Some(Duration::from_secs(30)));
+        assert_eq!(reloaded.grid_spacing, 700.0);
+        assert_eq!(reloaded.vehicle_types, scenario.vehicle_types);
+        assert_eq!(reloaded.policy, PolicyKind::Batch);
+        assert_eq!(reloaded.preemption_rule, PreemptionRule::HigherPriority);
+        assert_eq!(reloaded.conflict_overrides, scenario.conflict_overrides);
+        let rate = |scenario: &Scenario| {
+            let demand = scenario.demand.as_ref().unwrap();
+            demand.rate(crate::MovementDirection::Left, crate::TurnDirection::Right).unwrap().clone()
+        };
+        assert_eq!(rate(&reloaded), rate(&scenario));
+    }
+}
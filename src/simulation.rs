@@ -0,0 +1,814 @@
+use crate::collision::{CollisionDetector, Contact, ContactKind};
+use crate::geometry::Geometry;
+use crate::intersection_manager::{IntersectionManager, IntersectionPhase, ReservationCounts};
+use crate::network::RoadNetwork;
+use crate::path::exit_direction;
+use crate::route::Route;
+use crate::physics_engine::PhysicsEngine;
+use crate::policy::RESERVATION_DISTANCE;
+use crate::spatial_index::SpatialGrid;
+use crate::demand::DemandProfile;
+use crate::spawner::{SpawnOutcome, Spawner, APPROACHES, TURNS};
+use crate::stats::Statistics;
+use crate::trace::Tracer;
+use crate::vehicle::*;
+use crate::MovementDirection;
+use crate::TurnDirection;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::time::Duration;
+
+// Physics tick length used by the frontend, 60 ticks per simulated second
+pub const DEFAULT_TIMESTEP: Duration = Duration::from_nanos(1_000_000_000 / 60);
+// Upper bound on the real time fed into one `advance` call, so a stalled
+// frame does not make the simulation spiral into thousands of catch-up ticks
+pub const MAX_FRAME_TIME: Duration = Duration::from_millis(250);
+// Range of simulated seconds per real second the frontends allow
+pub const MIN_TIME_SCALE: f32 = 0.25;
+pub const MAX_TIME_SCALE: f32 = 16.0;
+// Default gap between two vehicles of the automatic traffic generator
+pub const AUTO_SPAWN_INTERVAL: Duration = Duration::from_millis(500);
+
+pub struct Simulation {
+    vehicles: Vec<Vehicle>,
+    network: RoadNetwork,
+    physics_engine: PhysicsEngine,
+    statistics: Statistics,
+    collision_detector: CollisionDetector,
+    spatial_grid: SpatialGrid,
+    contacts: Vec<Contact>,
+    halted: bool,
+    // Print reservation, intersection and contact events to stdout
+    log_events: bool,
+    tracer: Option<Tracer>,
+    // When set, a vehicle from a random approach is spawned every interval
+    auto_spawn_interval: Option<Duration>,
+    next_auto_spawn: Duration,
+    spawner: Spawner,
+    demand: Option<DemandProfile>,
+    // Spawned vehicles are drawn from these types in proportion to their weights
+    vehicle_types: Vec<VehicleType>,
+    next_vehicle_id: i32,
+    time: Duration,
+    // Number of ticks run so far
+    ticks: u64,
+    timestep: Duration,
+    accumulator: Duration,
+    // Simulated time per unit of real time fed into `advance`
+    time_scale: f32,
+    seed: u64,
+    // Every random decision in the simulation is drawn from this generator so
+    // that a seed plus the input sequence fully determines a run
+    rng: StdRng,
+}
+
+impl Simulation {
+    pub fn new(physics_engine: PhysicsEngine, timestep: Duration, seed: u64) -> Self {
+        assert!(!timestep.is_zero(), "simulation timestep must be positive");
+        Simulation {
+            vehicles: Vec::new(),
+            network: RoadNetwork::single(Geometry::default()),
+            physics_engine,
+            statistics: Statistics::new(),
+            collision_detector: CollisionDetector::default(),
+            spatial_grid: SpatialGrid::new(1.0), // resized on every rebuild
+            contacts: Vec::new(),
+            halted: false,
+            log_events: true,
+            tracer: None,
+            auto_spawn_interval: None,
+            next_auto_spawn: Duration::ZERO,
+            spawner: Spawner::new(),
+            demand: None,
+            vehicle_types: vec![VehicleType::default()],
+            next_vehicle_id: 1,
+            time: Duration::ZERO,
+            ticks: 0,
+            timestep,
+            accumulator: Duration::ZERO,
+            time_scale: 1.0,
+            seed,
+            rng: StdRng::seed_from_u64(seed),
+        }
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    // Current simulation clock, advanced by `timestep` on every tick
+    pub fn time(&self) -> Duration {
+        self.time
+    }
+
+    pub fn ticks(&self) -> u64 {
+        self.ticks
+    }
+
+    pub fn timestep(&self) -> Duration {
+        self.timestep
+    }
+
+    pub fn vehicles(&self) -> &[Vehicle] {
+        &self.vehicles
+    }
+
+    // Manager of the first intersection, the only one unless a larger network is set
+    pub fn intersection_manager(&self) -> &IntersectionManager {
+        self.network.manager(0)
+    }
+
+    pub fn geometry(&self) -> &Geometry {
+        self.network.node(0).geometry()
+    }
+
+    // Replace the network by a single intersection laid out by `geometry`
+    pub fn set_geometry(&mut self, geometry: Geometry) {
+        self.set_network(RoadNetwork::single(geometry));
+    }
+
+    pub fn network(&self) -> &RoadNetwork {
+        &self.network
+    }
+
+    // Replace the road network; meant to be called before any vehicle is spawned
+    pub fn set_network(&mut self, network: RoadNetwork) {
+        self.network = network;
+    }
+
+    // Reservation counts summed over all intersections
+    pub fn reservation_counts(&self) -> ReservationCounts {
+        let mut total = ReservationCounts::default();
+        for node in self.network.nodes() {
+            let counts = node.manager().reservation_counts();
+            total.active += counts.active;
+            total.expired += counts.expired;
+            total.cancelled += counts.cancelled;
+            total.revoked += counts.revoked;
+        }
+        total
+    }
+
+    // Give up the reservation the vehicle holds for the intersection ahead; it
+    // asks for a new one once close enough. False when it held none.
+    pub fn cancel_reservation(&mut self, vehicle_id: i32) -> bool {
+        let vehicle = match self.vehicles.iter_mut().find(|vehicle| vehicle.id == vehicle_id) {
+            Some(vehicle) => vehicle,
+            None => return false,
+        };
+        let node = match self.network.link(vehicle.link).to {
+            Some(node) => node,
+            None => return false,
+        };
+        let cancelled = self.network.manager_mut(node).cancel_reservation(vehicle_id);
+        if cancelled && vehicle.turn_progress.is_none() {
+            vehicle.reservation_status = ReservationStatus::NotRequested;
+        }
+        cancelled
+    }
+
+    pub fn physics_engine(&self) -> &PhysicsEngine {
+        &self.physics_engine
+    }
+
+    pub fn statistics(&self) -> &Statistics {
+        &self.statistics
+    }
+
+    pub fn set_collision_detector(&mut self, collision_detector: CollisionDetector) {
+        self.collision_detector = collision_detector;
+    }
+
+    // Every collision and close call detected so far, in detection order
+    pub fn contacts(&self) -> &[Contact] {
+        &self.contacts
+    }
+
+    // True once a collision stopped the run because the detector is set to halt on it
+    pub fn is_halted(&self) -> bool {
+        self.halted
+    }
+
+    pub fn set_log_events(&mut self, log_events: bool) {
+        self.log_events = log_events;
+    }
+
+    // Write vehicle states to `tracer` from the next tick on, or stop tracing with `None`
+    pub fn set_tracer(&mut self, tracer: Option<Tracer>) {
+        self.tracer = tracer;
+    }
+
+    // Flush the trace and report the first error writing it, if any
+    pub fn finish_trace(&mut self) -> std::io::Result<()> {
+        match self.tracer.as_mut() {
+            Some(tracer) => tracer.finish(),
+            None => Ok(()),
+        }
+    }
+
+    pub fn spawner(&self) -> &Spawner {
+        &self.spawner
+    }
+
+    // Spawn a vehicle travelling in `movement_direction` on one of the network's
+    // entry links for that direction, drawn at random when there are several
+    pub fn spawn(&mut self, movement_direction: MovementDirection, turn_direction: TurnDirection) -> SpawnOutcome {
+        let entries = self.network.entry_links_towards(movement_direction);
+        let link = if entries.len() > 1 { entries[self.rng.gen_range(0..entries.len())] } else { entries[0] };
+        self.spawn_on_link(link, turn_direction)
+    }
+
+    // Spawn a vehicle at the start of an entry link. If the entry is occupied, or
+    // earlier requests for the same lane are still waiting, the request joins
+    // that lane's backlog instead.
+    pub fn spawn_on_link(&mut self, link: usize, turn_direction: TurnDirection) -> SpawnOutcome {
+        assert!(self.network.link(link).from.is_none(), "vehicles can only be spawned on entry links");
+        if self.spawner.backlog(link, turn_direction) == 0 && self.entry_clear(link, turn_direction) {
+            return SpawnOutcome::Spawned(self.spawn_now(link, turn_direction));
+        }
+
+        if self.spawner.enqueue(link, turn_direction) {
+            self.statistics.record_spawn_queued();
+            SpawnOutcome::Queued
+        } else {
+            self.statistics.record_spawn_dropped();
+            SpawnOutcome::Dropped
+        }
+    }
+
+    fn spawn_now(&mut self, link: usize, turn_direction: TurnDirection) -> i32 {
+        let movement_direction = self.network.link(link).direction;
+        let (position, lane) = self.network.link_geometry(link).spawn_point(movement_direction, turn_direction);
+        let vehicle_type = self.random_vehicle_type();
+        let mut vehicle = Vehicle::new(movement_direction, turn_direction, vehicle_type.velocity, position, lane);
+        vehicle.size = vehicle_type.size;
+        vehicle.priority = vehicle_type.priority;
+        vehicle.route = self.plan_route(link, turn_direction);
+        vehicle.link = link;
+        self.add_vehicle(vehicle)
+    }
+
+    // Route starting with `turn_direction` at the first intersection. When that turn
+    // leads further into the network, the vehicle heads for a random exit along
+    // the shortest route from there.
+    fn plan_route(&mut self, link: usize, turn_direction: TurnDirection) -> Route {
+        let after_turn = match self.network.next_link(link, turn_direction) {
+            Some(after_turn) if self.network.link(after_turn).to.is_some() => after_turn,
+            _ => return Route::single(turn_direction),
+        };
+        let exits: Vec<usize> = self.network.exit_links().collect();
+        let destination = exits[self.rng.gen_range(0..exits.len())];
+        let rest = self.network.shortest_route(after_turn, destination).expect("every exit of a grid is reachable");
+
+        let mut turns = vec![turn_direction];
+        turns.extend_from_slice(rest.turns());
+        let route = Route::new(turns);
+        debug_assert_eq!(self.network.validate_route(link, &route), Ok(destination));
+        route
+    }
+
+    // Only drawn from the RNG when there is a choice, so runs with the single
+    // default type keep their random sequence
+    fn random_vehicle_type(&mut self) -> VehicleType {
+        if self.vehicle_types.len() == 1 {
+            return self.vehicle_types[0].clone();
+        }
+        let total: f32 = self.vehicle_types.iter().map(|vehicle_type| vehicle_type.weight).sum();
+        let mut pick = self.rng.gen::<f32>() * total;
+        for vehicle_type in &self.vehicle_types {
+            if pick < vehicle_type.weight {
+                return vehicle_type.clone();
+            }
+            pick -= vehicle_type.weight;
+        }
+        self.vehicle_types[self.vehicle_types.len() - 1].clone()
+    }
+
+    // True when the entry of `link` for `turn_direction` has room for a new vehicle.
+    // The entry has to have room for the largest and fastest vehicle type, since the
+    // type is only drawn once the vehicle is actually spawned. A new vehicle moves
+    // before car-following first slows it down, so it also needs the road it
+    // covers in that tick.
+    pub fn entry_clear(&self, link: usize, turn_direction: TurnDirection) -> bool {
+        let movement_direction = self.network.link(link).direction;
+        let (entry, _) = self.network.link_geometry(link).spawn_point(movement_direction, turn_direction);
+        let size = self.vehicle_types.iter().map(|vehicle_type| vehicle_type.size).fold(0.0, f32::max);
+        let velocity = self.vehicle_types.iter().map(|vehicle_type| vehicle_type.velocity).fold(0.0, f32::max);
+        let first_step = velocity.min(self.physics_engine.max_velocity()) * self.timestep.as_secs_f32();
+        let clearance = self.physics_engine.safety_distance() + first_step;
+
+        // No vehicle is larger than a grid cell, so only cells this close can hold
+        // one in the way; vehicles spawned since the last rebuild are checked as well
+        let reach = (size + self.spatial_grid.cell_size()) / 2.0 + clearance;
+        let indexed = self.spatial_grid.indexed().min(self.vehicles.len());
+        let nearby = self.spatial_grid.query(entry, reach).filter_map(|index| self.vehicles.get(index));
+        Spawner::entry_clear(nearby.chain(&self.vehicles[indexed..]), entry, size, clearance)
+    }
+
+    // Let one waiting vehicle into every lane whose entry has cleared
+    fn release_backlog(&mut self) {
+        let entries: Vec<usize> = self.network.entry_links().collect();
+        for link in entries {
+            for turn_direction in TURNS {
+                if self.spawner.backlog(link, turn_direction) > 0 && self.entry_clear(link, turn_direction) {
+                    self.spawner.dequeue(link, turn_direction);
+                    self.spawn_now(link, turn_direction);
+                }
+            }
+        }
+    }
+
+    // Insert an already built vehicle, assigning it a fresh id and the current spawn time
+    pub fn add_vehicle(&mut self, mut vehicle: Vehicle) -> i32 {
+        let id = self.next_vehicle_id;
+        vehicle.id = id;
+        vehicle.spawn_time = self.time;
+        self.vehicles.push(vehicle);
+        self.next_vehicle_id += 1;
+        id
+    }
+
+    pub fn auto_spawn_interval(&self) -> Option<Duration> {
+        self.auto_spawn_interval
+    }
+
+    // Enable the automatic traffic generator with the given interval, or disable it with `None`.
+    // The first vehicle is spawned on the next tick.
+    pub fn set_auto_spawn(&mut self, interval: Option<Duration>) {
+        if let Some(interval) = interval {
+            assert!(!interval.is_zero(), "auto spawn interval must be positive");
+        }
+        self.auto_spawn_interval = interval;
+        self.next_auto_spawn = self.time;
+    }
+
+    pub fn demand(&self) -> Option<&DemandProfile> {
+        self.demand.as_ref()
+    }
+
+    // Drive spawning from arrival processes; arrivals go through the spawner like any other request
+    pub fn set_demand(&mut self, demand: Option<DemandProfile>) {
+        self.demand = demand;
+    }
+
+    pub fn vehicle_types(&self) -> &[VehicleType] {
+        &self.vehicle_types
+    }
+
+    pub fn set_vehicle_types(&mut self, vehicle_types: Vec<VehicleType>) {
+        assert!(!vehicle_types.is_empty(), "at least one vehicle type is needed");
+        self.vehicle_types = vehicle_types;
+    }
+
+    // Spawn a vehicle on a random approach with a random turn
+    pub fn spawn_random(&mut self) -> SpawnOutcome {
+        let movement_direction = APPROACHES[self.rng.gen_range(0..APPROACHES.len())];
+        self.spawn_random_turn(movement_direction)
+    }
+
+    // Spawn a vehicle on the given approach with a turn drawn from the simulation RNG
+    pub fn spawn_random_turn(&mut self, movement_direction: MovementDirection) -> SpawnOutcome {
+        let turn_direction = random_turn_direction(&mut self.rng);
+        self.spawn(movement_direction, turn_direction)
+    }
+
+    pub fn time_scale(&self) -> f32 {
+        self.time_scale
+    }
+
+    // Speed up or slow down simulated time relative to the real time fed into
+    // `advance`, clamped to MIN_TIME_SCALE..=MAX_TIME_SCALE
+    pub fn set_time_scale(&mut self, time_scale: f32) {
+        self.time_scale = time_scale.clamp(MIN_TIME_SCALE, MAX_TIME_SCALE);
+    }
+
+    // Feed elapsed real time into the accumulator and run as many fixed ticks
+    // as fit into it; returns the number of ticks performed
+    pub fn advance(&mut self, frame_time: Duration) -> u32 {
+        let ticks = self.accumulate(frame_time);
+        for _ in 0..ticks {
+            self.step();
+        }
+        ticks
+    }
+
+    // Feed elapsed real time into the accumulator and take out the number of
+    // whole ticks it covers, for callers that need to act between ticks
+    pub fn accumulate(&mut self, frame_time: Duration) -> u32 {
+        self.accumulator += frame_time.min(MAX_FRAME_TIME).mul_f32(self.time_scale);
+
+        let mut ticks = 0;
+        while self.accumulator >= self.timestep {
+            self.accumulator -= self.timestep;
+            ticks += 1;
+        }
+        ticks
+    }
+
+    // Advance the simulation by exactly one fixed timestep
+    pub fn step(&mut self) {
+        if self.halted {
+            return;
+        }
+
+        self.release_backlog();
+
+        if let Some(interval) = self.auto_spawn_interval {
+            if self.time >= self.next_auto_spawn {
+                self.spawn_random();
+                self.next_auto_spawn += interval;
+            }
+        }
+
+        if let Some(demand) = &self.demand {
+            let arrivals = demand.arrivals(self.time, self.timestep, &mut self.rng);
+            for (movement_direction, turn_direction) in arrivals {
+                self.spawn(movement_direction, turn_direction);
+            }
+        }
+
+        let dt = self.timestep.as_secs_f32();
+        let now = self.time;
+        let log_events = self.log_events;
+        let network = &mut self.network;
+        let vehicles = &mut self.vehicles;
+
+        let mut adjustments: Vec<(usize, f32)> = Vec::new(); // Stores (index, new_speed)
+        let mut vehicle_pairs: Vec<(usize, usize)> = Vec::new();
+
+        for node in 0..network.nodes().len() {
+            network.manager_mut(node).tick(now);
+        }
+
+        for vehicle in vehicles.iter_mut() {
+            self.physics_engine.update(vehicle, dt);
+
+            // Hand vehicles that crossed their intersection over to the link they left on
+            if let Some(node) = network.link(vehicle.link).to.filter(|_| vehicle.crossed_intersection) {
+                vehicle.link = network.outgoing_link(node, vehicle.movement_direction());
+                if network.link(vehicle.link).to.is_some() {
+                    vehicle.route.advance();
+                    vehicle.lane = Lane::for_turn(vehicle.turn_direction());
+                    vehicle.crossed_intersection = false;
+                    vehicle.reservation_status = ReservationStatus::NotRequested;
+                }
+            }
+
+            let geometry = network.link_geometry(vehicle.link);
+            vehicle.update_distance_and_time_to_intersection(geometry);
+
+            // Check if vehicle is at the intersection
+            if vehicle.at_intersection_entry(geometry) {
+                // The route decides the turn; a vehicle in the wrong lane still follows
+                // it but the violation is reported
+                if let Err(e) = vehicle.check_lane(geometry) {
+                    if log_events {
+                        println!("Lane violation: {}", e);
+                    }
+                    self.statistics.record_lane_violation();
+                }
+                vehicle.update_direction_at_intersection(geometry);
+                vehicle.box_entry = network.link(vehicle.link).to.map(|node| (node, now));
+                if log_events {
+                    println!("Vehicle {} entered intersection with turn direction: {:?}", vehicle.id, vehicle.turn_direction());
+                }
+            }
+        }
+
+        // Neighbor queries only need to reach one vehicle length plus the safety distance
+        // and the road covered in one tick, beyond that neither the safety logic nor
+        // the collision checks react
+        let max_size = vehicles.iter().map(|v| v.size).fold(0.0, f32::max);
+        let max_step = vehicles.iter().map(|v| v.velocity.max(v.desired_velocity)).fold(0.0, f32::max) * dt;
+        let neighbor_radius = max_size + self.physics_engine.safety_distance() + max_step;
+        self.spatial_grid.rebuild(vehicles, neighbor_radius.max(1.0));
+
+        // Rear ends of vehicles slowed down or standing on a link, taken before the
+        // speeds are reset below
+        let queued: Vec<(usize, f32)> = vehicles
+            .iter()
+            .filter(|vehicle| vehicle.turn_progress.is_none() && vehicle.velocity < vehicle.desired_velocity)
+            .map(|vehicle| (vehicle.link, vehicle.longitudinal_position() - vehicle.size / 2.0))
+            .collect();
+
+        for i in 0..vehicles.len() {
+            // Back to cruising speed unless held at the stop line; the safety
+            // adjustments below slow it down again if the road ahead is not free
+            if vehicles[i].reservation_status != ReservationStatus::Waiting {
+                vehicles[i].velocity = vehicles[i].desired_velocity;
+            }
+
+            // A vehicle close enough to ask for a reservation looks for its leader as far
+            // as the stop line, so a leader waiting there still counts for the queue below
+            let may_request = !vehicles[i].crossed_intersection
+                && vehicles[i].turn_progress.is_none()
+                && vehicles[i].distance_to_intersection < RESERVATION_DISTANCE;
+            let radius = if may_request { RESERVATION_DISTANCE + max_size } else { self.spatial_grid.cell_size() };
+            let candidates = self.spatial_grid.query(vehicles[i].position, radius);
+            let manager = network.manager(network.reference_node(vehicles[i].link));
+            let vehicle_ahead = manager.get_vehicle_ahead_among(&vehicles[i], vehicles, candidates);
+            if let Some(vehicle_ahead_index) = vehicle_ahead {
+                vehicle_pairs.push((i, vehicle_ahead_index));
+            }
+            // Vehicles queue for the box in lane order: a reservation granted behind a
+            // vehicle still waiting for one could never be used and would block it
+            let first_in_queue = vehicle_ahead.is_none_or(|index| {
+                let ahead = &vehicles[index];
+                ahead.link != vehicles[i].link
+                    || ahead.turn_progress.is_some()
+                    || ahead.reservation_status == ReservationStatus::Granted
+            });
+
+            // The reservation for the intersection behind ends once the rear is out of its box
+            if let Some(node) = network.link(vehicles[i].link).from {
+                if network.manager(node).intersection_phase(&vehicles[i]) == IntersectionPhase::Exited {
+                    if let Some((_, entry_time)) = vehicles[i].box_entry.take_if(|(entered, _)| *entered == node) {
+                        self.statistics.record_intersection_crossed(now - entry_time);
+                    }
+                    if network.manager_mut(node).release_reservation(vehicles[i].id) && log_events {
+                        println!("Reservation released for vehicle {}", vehicles[i].id);
+                    }
+                }
+            }
+
+            let node = match network.link(vehicles[i].link).to {
+                Some(node) => node,
+                None => continue, // leaving the world, no intersection ahead
+            };
+
+            // Vehicles do not enter a box they could not leave, or a queue spilling back
+            // from the next intersection would block this one for every movement. A
+            // granted vehicle whose front is already past the stop line goes on.
+            let committed = vehicles[i].reservation_status == ReservationStatus::Granted
+                && vehicles[i].distance_to_intersection <= 0.0;
+            if !committed
+                && !vehicles[i].crossed_intersection
+                && vehicles[i].turn_progress.is_none()
+                && vehicles[i].distance_to_intersection < RESERVATION_DISTANCE
+                && exit_blocked(network, &queued, vehicles, i, node, self.physics_engine.safety_distance())
+            {
+                // Its permission, or a request the policy still holds for later, would
+                // be for a crossing time the vehicle is going to miss
+                network.manager_mut(node).cancel_reservation(vehicles[i].id);
+                vehicles[i].reservation_status = ReservationStatus::Waiting;
+                vehicles[i].velocity = 0.0;
+                continue;
+            }
+
+            // A granted vehicle held up, or running early, by more than the time margin no
+            // longer matches the tiles it claimed, and one whose reservation ran out before
+            // it got to the box holds none; it cancels and asks again right away
+            if vehicles[i].reservation_status == ReservationStatus::Granted && vehicles[i].turn_progress.is_none() {
+                let manager = network.manager_mut(node);
+                let stale = match manager.reservation(vehicles[i].id) {
+                    Some(reservation) => {
+                        let (start_time, _) = manager.calculate_reservation_window(&vehicles[i], now);
+                        start_time.abs_diff(reservation.start_time) > manager.tile_grid().config().time_margin
+                    }
+                    None => true,
+                };
+                if stale {
+                    manager.cancel_reservation(vehicles[i].id);
+                    vehicles[i].reservation_status = ReservationStatus::NotRequested;
+                }
+            }
+            // Vehicles without permission come to rest on the policy's stop line instead of
+            // running past it within one tick, however coarse the timestep
+            if let Some(stop_line) = network.manager(node).stop_line() {
+                let to_line = vehicles[i].distance_to_intersection - stop_line;
+                if vehicles[i].reservation_status != ReservationStatus::Granted
+                    && !vehicles[i].crossed_intersection
+                    && vehicles[i].turn_progress.is_none()
+                    && to_line >= 0.0
+                {
+                    vehicles[i].velocity = vehicles[i].velocity.min(to_line / dt);
+                }
+            }
+            if
+                vehicles[i].reservation_status != ReservationStatus::Granted &&
+                first_in_queue &&
+                !vehicles[i].crossed_intersection &&
+                vehicles[i].turn_progress.is_none() &&
+                vehicles[i].distance_to_intersection < network.manager(node).request_distance()
+            {
+                let waiting = vehicles[i].reservation_status == ReservationStatus::Waiting;
+                match network.manager_mut(node).request_reservation(&vehicles[i], now) {
+                    Ok(_) => {
+                        if log_events {
+                            println!("Reservation granted for vehicle {}", vehicles[i].id);
+                        }
+                        vehicles[i].reservation_status = ReservationStatus::Granted;
+                        vehicles[i].velocity = vehicles[i].desired_velocity;
+                    }
+                    Err(e) => {
+                        // Denied vehicles stop and ask again on the next tick
+                        if log_events && !waiting {
+                            println!("Reservation error for vehicle {}: {}", vehicles[i].id, e);
+                        }
+                        vehicles[i].reservation_status = ReservationStatus::Waiting;
+                        vehicles[i].velocity = 0.0;
+                    }
+                }
+            }
+        }
+
+        // Vehicles that lost their reservation to a preempting one stop before the box
+        // and ask again on the next tick
+        for node in 0..network.nodes().len() {
+            for vehicle_id in network.manager_mut(node).take_revocations() {
+                if let Some(vehicle) = vehicles.iter_mut().find(|vehicle| vehicle.id == vehicle_id) {
+                    if log_events {
+                        println!("Reservation revoked for vehicle {}", vehicle_id);
+                    }
+                    vehicle.reservation_status = ReservationStatus::Waiting;
+                    vehicle.velocity = 0.0;
+                }
+            }
+        }
+
+        // A vehicle slowing down can force the one behind it to slow down too, so the
+        // adjustments are repeated until they no longer change any speed. Speeds only
+        // ever decrease, which bounds the number of passes by the longest queue.
+        for _ in 0..=vehicle_pairs.len() {
+            for &(vehicle_index, vehicle_ahead_index) in &vehicle_pairs {
+                let new_speed = self.physics_engine.adjust_speed_for_safety(
+                    &vehicles[vehicle_index],
+                    &vehicles[vehicle_ahead_index],
+                    dt
+                );
+                if new_speed < vehicles[vehicle_index].velocity {
+                    adjustments.push((vehicle_index, new_speed));
+                }
+            }
+            if adjustments.is_empty() {
+                break;
+            }
+            for (index, new_speed) in adjustments.drain(..) {
+                vehicles[index].velocity = new_speed;
+            }
+        }
+
+        for vehicle in vehicles.iter() {
+            self.statistics.record_velocity(vehicle.velocity);
+        }
+
+        self.time += self.timestep;
+        self.ticks += 1;
+
+        let contacts = self.collision_detector.detect(vehicles, &self.spatial_grid, self.physics_engine.safety_distance(), self.time);
+        for contact in contacts {
+            let (first_id, second_id) = contact.vehicle_ids;
+            match contact.kind {
+                ContactKind::Collision => {
+                    if log_events {
+                        println!("Collision between vehicles {} and {} at {:?}", first_id, second_id, contact.time);
+                    }
+                    self.statistics.record_collision();
+                    if self.collision_detector.halt_on_collision() {
+                        self.halted = true;
+                    }
+                }
+                ContactKind::CloseCall => {
+                    if log_events {
+                        println!("Close call between vehicles {} and {} at {:?}", first_id, second_id, contact.time);
+                    }
+                    self.statistics.record_close_call(first_id, second_id);
+                }
+            }
+            self.contacts.push(contact);
+        }
+
+        // Sampled before removing vehicles that left, so their last position is traced
+        if let Some(tracer) = self.tracer.as_mut() {
+            tracer.sample(self.time, vehicles);
+        }
+
+        let exit_time = self.time;
+        let before = vehicles.len();
+        vehicles.retain(|vehicle| {
+            let inside = !network.link_geometry(vehicle.link).has_left_world(vehicle.position, vehicle.movement_direction());
+            if !inside {
+                self.statistics.record_vehicle_passed(exit_time - vehicle.spawn_time, vehicle.distance_travelled);
+            }
+            inside
+        });
+
+        // Leaving vehicles shift the indices behind them. Spawns between two ticks
+        // look up occupied entries in the grid, so it has to match the list again.
+        if vehicles.len() != before {
+            let cell_size = self.spatial_grid.cell_size();
+            self.spatial_grid.rebuild(vehicles, cell_size);
+        }
+    }
+}
+
+// True when a vehicle in `queued` stands or crawls on the link `vehicles[index]` leaves
+// `node` by, closer to the box than the room it needs there. Before its grant the room
+// also holds the vehicles already granted through the box towards the same link, which
+// get there first; a granted vehicle is not turned back for the ones granted after it.
+fn exit_blocked(
+    network: &RoadNetwork,
+    queued: &[(usize, f32)],
+    vehicles: &[Vehicle],
+    index: usize,
+    node: usize,
+    safety_distance: f32,
+) -> bool {
+    let exit_direction = vehicle_exit_direction(&vehicles[index]);
+    let exit = network.outgoing_link(node, exit_direction);
+    let (_, box_end) = network.manager(node).geometry().intersection_extent(exit_direction);
+    let granted = vehicles[index].reservation_status == ReservationStatus::Granted;
+    let ahead: f32 = vehicles
+        .iter()
+        .enumerate()
+        .filter(|&(other_index, other)| {
+            !granted
+                && other_index != index
+                && other.reservation_status == ReservationStatus::Granted
+                && network.link(other.link).to == Some(node)
+                && vehicle_exit_direction(other) == exit_direction
+        })
+        .map(|(_, other)| other.size + safety_distance)
+        .sum();
+    let room = box_end + ahead + vehicles[index].size + safety_distance;
+    queued.iter().any(|&(link, rear)| link == exit && rear < room)
+}
+
+// Direction a vehicle leaves the intersection ahead of it by
+fn vehicle_exit_direction(vehicle: &Vehicle) -> MovementDirection {
+    match &vehicle.turn_progress {
+        Some(progress) => progress.exit_direction,
+        None => exit_direction(vehicle.movement_direction(), vehicle.turn_direction()),
+    }
+}
+
+fn random_turn_direction(rng: &mut StdRng) -> TurnDirection {
+    if rng.gen::<f32>() < 0.33 {
+        TurnDirection::Left
+    } else if rng.gen::<f32>() < 0.5 {
+        TurnDirection::Straight
+    } else {
+        TurnDirection::Right
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::scenario::Scenario;
+    use crate::Simulation;
+
+    // Run the scenario for its duration
+    fn run(json: &str) -> Simulation {
+        let scenario = Scenario::from_json(json).unwrap();
+        let mut simulation = scenario.build(1).unwrap();
+        while simulation.time() < scenario.duration.unwrap() {
+            simulation.step();
+        }
+        simulation
+    }
+
+    #[test]
+    fn all_way_stop_admits_vehicles_at_a_coarse_timestep() {
+        // One tick covers 30 units at the default speed, more than the stop line tolerance
+        let simulation = run(r#"{
+            "seed": 1, "duration": 60, "timestep": 0.05, "policy": "all_way_stop",
+            "demand": [{ "approach": "up", "turn": "straight", "rate": 0.2 }]
+        }"#);
+        let statistics = simulation.statistics();
+        assert!(statistics.vehicles_passed > 0);
+        assert_eq!(statistics.collisions, 0);
+    }
+
+    // Three by three intersections fed from every approach with every turn, so queues
+    // spill back from one intersection to the box of the one before
+    fn grid(policy: &str, seed: u64) -> Simulation {
+        let demand: Vec<String> = ["up", "down", "left", "right"]
+            .iter()
+            .flat_map(|approach| {
+                ["left", "straight", "right"].iter().map(move |turn| {
+                    format!(r#"{{ "approach": "{}", "turn": "{}", "rate": 0.3 }}"#, approach, turn)
+                })
+            })
+            .collect();
+        run(&format!(
+            r#"{{ "seed": {}, "duration": 180, "policy": "{}", "grid": {{ "rows": 3, "cols": 3 }}, "demand": [{}] }}"#,
+            seed,
+            policy,
+            demand.join(", ")
+        ))
+    }
+
+    #[test]
+    fn turning_vehicles_keep_clear_of_full_exit_links() {
+        let simulation = grid("fcfs", 3);
+        assert!(simulation.statistics().vehicles_passed > 0);
+        assert_eq!(simulation.statistics().collisions, 0);
+    }
+
+    #[test]
+    fn batch_grants_keep_clear_of_full_exit_links() {
+        let simulation = grid("batch", 3);
+        assert!(simulation.statistics().vehicles_passed > 0);
+        assert_eq!(simulation.statistics().collisions, 0);
+    }
+}
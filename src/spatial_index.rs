@@ -0,0 +1,142 @@
+use crate::vehicle::Vehicle;
+use crate::Position;
+use std::collections::HashMap;
+
+// Uniform grid over vehicle positions, rebuilt every tick, so neighbor queries
+// only look at nearby cells instead of scanning every vehicle
+#[derive(Debug, Clone)]
+pub struct SpatialGrid {
+    cell_size: f32,
+    cells: HashMap<(i32, i32), Vec<usize>>,
+    // Number of vehicles bucketed by the last rebuild
+    indexed: usize,
+}
+
+impl SpatialGrid {
+    pub fn new(cell_size: f32) -> Self {
+        assert!(cell_size > 0.0, "grid cell size must be positive");
+        SpatialGrid {
+            cell_size,
+            cells: HashMap::new(),
+            indexed: 0,
+        }
+    }
+
+    pub fn cell_size(&self) -> f32 {
+        self.cell_size
+    }
+
+    // Vehicles added after the last rebuild have indices from here on and are not
+    // in the grid yet
+    pub fn indexed(&self) -> usize {
+        self.indexed
+    }
+
+    // Re-bucket all vehicles by index. Cell vectors are kept allocated between
+    // ticks, empty ones are dropped once they stay unused for a rebuild.
+    pub fn rebuild(&mut self, vehicles: &[Vehicle], cell_size: f32) {
+        assert!(cell_size > 0.0, "grid cell size must be positive");
+        if cell_size != self.cell_size {
+            self.cell_size = cell_size;
+            self.cells.clear();
+        }
+
+        self.cells.retain(|_, indices| !indices.is_empty());
+        for indices in self.cells.values_mut() {
+            indices.clear();
+        }
+        for (index, vehicle) in vehicles.iter().enumerate() {
+            let cell = self.cell_of(vehicle.position);
+            self.cells.entry(cell).or_default().push(index);
+        }
+        self.indexed = vehicles.len();
+    }
+
+    // Indices of all vehicles whose cell intersects the square of half-size
+    // `radius` around `center`. Results may include vehicles slightly further
+    // than `radius`; callers apply their own exact distance checks.
+    pub fn query(&self, center: Position, radius: f32) -> impl Iterator<Item = usize> + '_ {
+        let (min_x, min_y) = self.cell_of(Position::new(center.x - radius, center.y - radius));
+        let (max_x, max_y) = self.cell_of(Position::new(center.x + radius, center.y + radius));
+
+        (min_x..=max_x)
+            .flat_map(move |cx| (min_y..=max_y).map(move |cy| (cx, cy)))
+            .filter_map(move |cell| self.cells.get(&cell))
+            .flat_map(|indices| indices.iter().copied())
+    }
+
+    fn cell_of(&self, position: Position) -> (i32, i32) {
+        (
+            (position.x / self.cell_size).floor() as i32,
+            (position.y / self.cell_size).floor() as i32,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vehicle::Lane;
+    use crate::MovementDirection;
+    use crate::TurnDirection;
+
+    fn vehicles_at(positions: &[(f32, f32)]) -> Vec<Vehicle> {
+        positions
+            .iter()
+            .map(|&(x, y)| {
+                Vehicle::new(MovementDirection::Up, TurnDirection::Straight, 600.0, Position::new(x, y), Lane::Middle)
+            })
+            .collect()
+    }
+
+    fn sorted(grid: &SpatialGrid, center: (f32, f32), radius: f32) -> Vec<usize> {
+        let mut indices: Vec<usize> = grid.query(Position::new(center.0, center.1), radius).collect();
+        indices.sort();
+        indices
+    }
+
+    #[test]
+    fn query_reaches_across_cell_boundaries() {
+        let vehicles = vehicles_at(&[(99.0, 50.0), (101.0, 50.0), (50.0, 199.5), (-1.0, -1.0)]);
+        let mut grid = SpatialGrid::new(100.0);
+        grid.rebuild(&vehicles, 100.0);
+
+        // Neighbours on both sides of a cell edge, including negative coordinates.
+        // Whole cells are returned, so the first vehicle comes along as well.
+        assert_eq!(sorted(&grid, (100.0, 50.0), 5.0), vec![0, 1]);
+        assert_eq!(sorted(&grid, (1.0, 1.0), 5.0), vec![0, 3]);
+        assert_eq!(sorted(&grid, (50.0, 201.0), 2.0), vec![2]);
+    }
+
+    #[test]
+    fn query_covers_the_whole_radius() {
+        let vehicles = vehicles_at(&[(0.5, 0.5), (250.0, 0.5), (350.0, 0.5)]);
+        let mut grid = SpatialGrid::new(100.0);
+        grid.rebuild(&vehicles, 100.0);
+
+        // A radius larger than a cell reaches several cells out
+        assert_eq!(sorted(&grid, (0.5, 0.5), 250.0), vec![0, 1]);
+        // Exactly at the radius still counts, the cell holding it is visited
+        assert_eq!(sorted(&grid, (0.5, 0.5), 249.5), vec![0, 1]);
+        // A zero radius only looks at the center's own cell
+        assert_eq!(sorted(&grid, (0.5, 0.5), 0.0), vec![0]);
+        assert!(sorted(&grid, (1000.0, 1000.0), 10.0).is_empty());
+    }
+
+    #[test]
+    fn rebuild_follows_moves_and_cell_size_changes() {
+        let mut vehicles = vehicles_at(&[(10.0, 10.0), (20.0, 10.0)]);
+        let mut grid = SpatialGrid::new(50.0);
+        grid.rebuild(&vehicles, 50.0);
+        assert_eq!(sorted(&grid, (10.0, 10.0), 1.0), vec![0, 1]);
+
+        vehicles[1].position = Position::new(500.0, 10.0);
+        grid.rebuild(&vehicles, 50.0);
+        assert_eq!(sorted(&grid, (10.0, 10.0), 1.0), vec![0]);
+        assert_eq!(sorted(&grid, (500.0, 10.0), 1.0), vec![1]);
+
+        grid.rebuild(&vehicles, 1000.0);
+        assert_eq!(grid.cell_size(), 1000.0);
+        assert_eq!(sorted(&grid, (10.0, 10.0), 1.0), vec![0, 1]);
+    }
+}
@@ -0,0 +1,85 @@
+use crate::vehicle::Vehicle;
+use crate::MovementDirection;
+use crate::Position;
+use crate::TurnDirection;
+use std::collections::HashMap;
+
+// Spawn requests that can wait per entry lane before further ones are dropped
+pub const MAX_SPAWN_BACKLOG: u32 = 10;
+
+pub const APPROACHES: [MovementDirection; 4] = [
+    MovementDirection::Up,
+    MovementDirection::Down,
+    MovementDirection::Left,
+    MovementDirection::Right,
+];
+
+pub const TURNS: [TurnDirection; 3] = [TurnDirection::Left, TurnDirection::Straight, TurnDirection::Right];
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum SpawnOutcome {
+    Spawned(i32),
+    // The entry was occupied, the vehicle will enter once it clears
+    Queued,
+    // The entry was occupied and the lane backlog is full
+    Dropped,
+}
+
+// Keeps vehicles from being stacked onto an occupied entry. Each entry lane,
+// identified by entry link and turn, has its own backlog of pending spawns.
+#[derive(Debug, Clone, Default)]
+pub struct Spawner {
+    backlog: HashMap<(usize, TurnDirection), u32>,
+}
+
+impl Spawner {
+    pub fn new() -> Self {
+        Spawner::default()
+    }
+
+    pub fn backlog(&self, link: usize, turn_direction: TurnDirection) -> u32 {
+        self.backlog.get(&(link, turn_direction)).copied().unwrap_or(0)
+    }
+
+    pub fn total_backlog(&self) -> u32 {
+        self.backlog.values().sum()
+    }
+
+    // Queue a spawn for the lane; returns false when its backlog is already full
+    pub fn enqueue(&mut self, link: usize, turn_direction: TurnDirection) -> bool {
+        let queued = self.backlog.entry((link, turn_direction)).or_insert(0);
+        if *queued >= MAX_SPAWN_BACKLOG {
+            return false;
+        }
+        *queued += 1;
+        true
+    }
+
+    // Take one pending spawn off the lane backlog, if any
+    pub fn dequeue(&mut self, link: usize, turn_direction: TurnDirection) -> bool {
+        match self.backlog.get_mut(&(link, turn_direction)) {
+            Some(queued) if *queued > 0 => {
+                *queued -= 1;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    // The entry is occupied while any of `vehicles` is closer than `safety_distance`
+    // to a vehicle of `size` standing on the spawn point `entry`. Callers may pass
+    // only the vehicles near the entry.
+    pub fn entry_clear<'a>(
+        vehicles: impl IntoIterator<Item = &'a Vehicle>,
+        entry: Position,
+        size: f32,
+        safety_distance: f32,
+    ) -> bool {
+        vehicles.into_iter().all(|v| {
+            let half_extent = (size + v.size) / 2.0;
+            let gap_x = ((v.position.x - entry.x).abs() - half_extent).max(0.0);
+            let gap_y = ((v.position.y - entry.y).abs() - half_extent).max(0.0);
+            gap_x.hypot(gap_y) >= safety_distance
+        })
+    }
+}
@@ -0,0 +1,156 @@
+use serde::Serialize;
+use std::collections::HashSet;
+use std::time::Duration;
+
+// Aggregate numbers collected over a whole run, used to compare
+// intersection management policies
+#[derive(Debug, Default, Clone, Serialize)]
+pub struct Statistics {
+    pub vehicles_passed: u32,
+    // Highest and lowest speed any vehicle reached while moving; standing
+    // vehicles are left out so the minimum does not just say someone queued
+    pub max_velocity: Option<f32>,
+    pub min_velocity: Option<f32>,
+    // Average speed of a vehicle over its whole trip, the distance it travelled
+    // divided by its trip time, so time spent queued counts as slow
+    pub max_average_velocity: Option<f32>,
+    pub min_average_velocity: Option<f32>,
+    // Time from the front entering an intersection box until the rear leaves it,
+    // in seconds, counted once per intersection crossed
+    pub max_crossing_time: Option<f32>,
+    pub min_crossing_time: Option<f32>,
+    // Time from spawning until leaving the world, in seconds
+    pub max_trip_time: Option<f32>,
+    pub min_trip_time: Option<f32>,
+    pub close_calls: u32,
+    pub collisions: u32,
+    // Spawn requests that had to wait for an occupied entry, and those that were lost
+    pub spawns_queued: u32,
+    pub spawns_dropped: u32,
+    // Vehicles that reached an intersection in a lane not allowing their route's turn
+    pub lane_violations: u32,
+
+    // Vehicle id pairs already counted as a close call, so a pair that stays
+    // too close for several ticks is reported once
+    #[serde(skip)]
+    close_call_pairs: HashSet<(i32, i32)>,
+}
+
+impl Statistics {
+    pub fn new() -> Self {
+        Statistics::default()
+    }
+
+    // Speed of a vehicle during one tick; standing vehicles are ignored
+    pub fn record_velocity(&mut self, velocity: f32) {
+        if velocity > 0.0 {
+            record_range(&mut self.min_velocity, &mut self.max_velocity, velocity);
+        }
+    }
+
+    // A vehicle's rear left the box it entered at `crossing_time` before
+    pub fn record_intersection_crossed(&mut self, crossing_time: Duration) {
+        record_range(&mut self.min_crossing_time, &mut self.max_crossing_time, crossing_time.as_secs_f32());
+    }
+
+    pub fn record_vehicle_passed(&mut self, trip_time: Duration, distance_travelled: f32) {
+        let seconds = trip_time.as_secs_f32();
+        self.vehicles_passed += 1;
+        if seconds > 0.0 {
+            let velocity = distance_travelled / seconds;
+            record_range(&mut self.min_average_velocity, &mut self.max_average_velocity, velocity);
+        }
+        record_range(&mut self.min_trip_time, &mut self.max_trip_time, seconds);
+    }
+
+    // Returns true if this is the first close call recorded for the pair
+    pub fn record_close_call(&mut self, vehicle_id: i32, other_id: i32) -> bool {
+        let pair = (vehicle_id.min(other_id), vehicle_id.max(other_id));
+        if self.close_call_pairs.insert(pair) {
+            self.close_calls += 1;
+            return true;
+        }
+        false
+    }
+
+    pub fn record_collision(&mut self) {
+        self.collisions += 1;
+    }
+
+    pub fn record_spawn_queued(&mut self) {
+        self.spawns_queued += 1;
+    }
+
+    pub fn record_spawn_dropped(&mut self) {
+        self.spawns_dropped += 1;
+    }
+
+    pub fn record_lane_violation(&mut self) {
+        self.lane_violations += 1;
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("statistics are always serializable")
+    }
+
+    // Human readable lines for the summary screen
+    pub fn summary_lines(&self) -> Vec<String> {
+        fn format(value: Option<f32>, unit: &str) -> String {
+            match value {
+                Some(v) => format!("{:.2} {}", v, unit),
+                None => "-".to_string(),
+            }
+        }
+
+        vec![
+            format!("Vehicles passed: {}", self.vehicles_passed),
+            format!("Max velocity: {}", format(self.max_velocity, "px/s")),
+            format!("Min velocity: {}", format(self.min_velocity, "px/s")),
+            format!("Max average velocity: {}", format(self.max_average_velocity, "px/s")),
+            format!("Min average velocity: {}", format(self.min_average_velocity, "px/s")),
+            format!("Max crossing time: {}", format(self.max_crossing_time, "s")),
+            format!("Min crossing time: {}", format(self.min_crossing_time, "s")),
+            format!("Max trip time: {}", format(self.max_trip_time, "s")),
+            format!("Min trip time: {}", format(self.min_trip_time, "s")),
+            format!("Close calls: {}", self.close_calls),
+            format!("Collisions: {}", self.collisions),
+            format!("Spawns queued: {}", self.spawns_queued),
+            format!("Spawns dropped: {}", self.spawns_dropped),
+            format!("Lane violations: {}", self.lane_violations),
+        ]
+    }
+}
+
+fn record_range(min: &mut Option<f32>, max: &mut Option<f32>, value: f32) {
+    *min = Some(min.map_or(value, |v| v.min(value)));
+    *max = Some(max.map_or(value, |v| v.max(value)));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn velocity_range_ignores_standing_vehicles() {
+        let mut statistics = Statistics::new();
+        for velocity in [0.0, 450.0, 600.0, 0.0, 300.0] {
+            statistics.record_velocity(velocity);
+        }
+        assert_eq!((statistics.min_velocity, statistics.max_velocity), (Some(300.0), Some(600.0)));
+    }
+
+    #[test]
+    fn crossings_and_trips_are_kept_apart() {
+        let mut statistics = Statistics::new();
+        statistics.record_intersection_crossed(Duration::from_millis(500));
+        statistics.record_intersection_crossed(Duration::from_millis(250));
+        statistics.record_vehicle_passed(Duration::from_secs(4), 1200.0);
+        statistics.record_vehicle_passed(Duration::from_secs(2), 1200.0);
+
+        assert_eq!(statistics.vehicles_passed, 2);
+        assert_eq!((statistics.min_crossing_time, statistics.max_crossing_time), (Some(0.25), Some(0.5)));
+        assert_eq!((statistics.min_trip_time, statistics.max_trip_time), (Some(2.0), Some(4.0)));
+        assert_eq!((statistics.min_average_velocity, statistics.max_average_velocity), (Some(300.0), Some(600.0)));
+        assert_eq!(statistics.max_velocity, None);
+    }
+}
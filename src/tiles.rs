@@ -0,0 +1,261 @@
+use crate::collision::project;
+use crate::geometry::Geometry;
+use crate::path::Path;
+use crate::vehicle::footprint;
+use crate::Position;
+use std::collections::HashSet;
+use std::time::Duration;
+
+// A tile of the intersection box during one time slot
+pub type TileSlot = (usize, u64);
+
+// How finely the intersection box is divided in space and time
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TileConfig {
+    // The box is split into tiles_per_side x tiles_per_side square tiles
+    pub tiles_per_side: usize,
+    // Length of one time slot
+    pub slot: Duration,
+    // Extra distance around the footprint that is claimed as well
+    pub buffer: f32,
+    // Extra time before and after each position that is claimed as well, absorbing
+    // small differences between the predicted and the actual arrival
+    pub time_margin: Duration,
+}
+
+impl Default for TileConfig {
+    fn default() -> Self {
+        TileConfig {
+            tiles_per_side: 12,
+            slot: Duration::from_millis(50),
+            buffer: 5.0,
+            time_margin: Duration::from_millis(100),
+        }
+    }
+}
+
+impl TileConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.tiles_per_side == 0 {
+            return Err("tiles_per_side must be at least 1".to_string());
+        }
+        if self.slot.is_zero() {
+            return Err("slot must be positive".to_string());
+        }
+        if self.buffer.is_nan() || self.buffer < 0.0 {
+            return Err(format!("buffer must not be negative, got {}", self.buffer));
+        }
+        Ok(())
+    }
+}
+
+// Space-time discretization of one intersection box (AIM style). A vehicle
+// crossing the box claims every tile its footprint touches, for every slot it
+// touches it; two crossings conflict when they share a claim.
+#[derive(Debug, Clone)]
+pub struct TileGrid {
+    config: TileConfig,
+    // Top-left corner of the box
+    origin: Position,
+    tile_size: f32,
+}
+
+impl TileGrid {
+    pub fn new(geometry: &Geometry, config: TileConfig) -> Self {
+        let (min, max) = geometry.intersection_bounds();
+        TileGrid {
+            config,
+            origin: min,
+            tile_size: (max.x - min.x) / config.tiles_per_side as f32,
+        }
+    }
+
+    pub fn config(&self) -> &TileConfig {
+        &self.config
+    }
+
+    pub fn tile_size(&self) -> f32 {
+        self.tile_size
+    }
+
+    // Top-left corner of `tile`, numbered row by row from the top-left of the box
+    pub fn tile_origin(&self, tile: usize) -> Position {
+        let (row, col) = (tile / self.config.tiles_per_side, tile % self.config.tiles_per_side);
+        Position::new(
+            self.origin.x + col as f32 * self.tile_size,
+            self.origin.y + row as f32 * self.tile_size,
+        )
+    }
+
+    pub fn slot_at(&self, time: Duration) -> u64 {
+        (time.as_nanos() / self.config.slot.as_nanos()) as u64
+    }
+
+    // Claims of a vehicle of side `size` following `path` at constant `speed`, whose
+    // front reaches the start of the path at `entry_time`. The footprint is swept
+    // from the front entering the box until the rear has left it.
+    pub fn claims(&self, path: &Path, size: f32, entry_time: Duration, speed: f32) -> HashSet<TileSlot> {
+        let mut claims = HashSet::new();
+        let claimed_size = size + 2.0 * self.config.buffer;
+        // Small enough steps that consecutive footprints overlap and no slot is skipped
+        let step = (self.tile_size / 2.0).min(speed * self.config.slot.as_secs_f32()).max(0.01);
+
+        // `distance` is the position of the vehicle center along the path
+        let mut distance = -size / 2.0;
+        let end = path.length() + size / 2.0;
+        while distance <= end {
+            let time = entry_time + Duration::from_secs_f32((distance + size / 2.0) / speed);
+            let next_time = time + Duration::from_secs_f32(step / speed);
+            let first_slot = self.slot_at(time.saturating_sub(self.config.time_margin));
+            let last_slot = self.slot_at(next_time + self.config.time_margin);
+
+            let (position, heading) = extended_sample(path, distance);
+            for tile in self.tiles_under(&footprint(position, heading, claimed_size)) {
+                claims.extend((first_slot..=last_slot).map(|slot| (tile, slot)));
+            }
+            distance += step;
+        }
+        claims
+    }
+
+    // Tiles overlapped by a convex quadrilateral
+    fn tiles_under(&self, corners: &[Position; 4]) -> Vec<usize> {
+        let n = self.config.tiles_per_side;
+        let min_x = corners.iter().map(|c| c.x).fold(f32::MAX, f32::min);
+        let max_x = corners.iter().map(|c| c.x).fold(f32::MIN, f32::max);
+        let min_y = corners.iter().map(|c| c.y).fold(f32::MAX, f32::min);
+        let max_y = corners.iter().map(|c| c.y).fold(f32::MIN, f32::max);
+
+        let index_range = |min: f32, max: f32, origin: f32| -> Option<(usize, usize)> {
+            let first = ((min - origin) / self.tile_size).floor();
+            let last = ((max - origin) / self.tile_size).ceil() - 1.0;
+            if last < 0.0 || first >= n as f32 {
+                return None; // entirely outside the box
+            }
+            Some((first.max(0.0) as usize, (last as usize).min(n - 1)))
+        };
+        let (Some((first_col, last_col)), Some((first_row, last_row))) =
+            (index_range(min_x, max_x, self.origin.x), index_range(min_y, max_y, self.origin.y))
+        else {
+            return Vec::new();
+        };
+
+        // The bounding box already separates along x and y; the footprint's own
+        // edge normals are the remaining separating axes to check
+        let axes = [
+            (corners[1].x - corners[0].x, corners[1].y - corners[0].y),
+            (corners[2].x - corners[1].x, corners[2].y - corners[1].y),
+        ];
+        let mut tiles = Vec::new();
+        for row in first_row..=last_row {
+            for col in first_col..=last_col {
+                let tile = row * n + col;
+                let origin = self.tile_origin(tile);
+                let tile_corners = [
+                    origin,
+                    Position::new(origin.x + self.tile_size, origin.y),
+                    Position::new(origin.x + self.tile_size, origin.y + self.tile_size),
+                    Position::new(origin.x, origin.y + self.tile_size),
+                ];
+                let separated = axes.iter().any(|&axis| {
+                    let (min_a, max_a) = project(corners, axis);
+                    let (min_b, max_b) = project(&tile_corners, axis);
+                    max_a <= min_b || max_b <= min_a
+                });
+                if !separated {
+                    tiles.push(tile);
+                }
+            }
+        }
+        tiles
+    }
+}
+
+// Position and heading `distance` along `path`, continuing straight along the
+// end headings before its start and after its end
+fn extended_sample(path: &Path, distance: f32) -> (Position, f32) {
+    let beyond = if distance < 0.0 { distance } else { (distance - path.length()).max(0.0) };
+    let (position, heading) = path.sample(distance);
+    (
+        Position::new(position.x + heading.cos() * beyond, position.y + heading.sin() * beyond),
+        heading,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::path::turning_path;
+    use crate::MovementDirection;
+    use crate::TurnDirection;
+
+    const SIZE: f32 = 55.0;
+    const SPEED: f32 = 600.0;
+
+    fn claims(
+        grid: &TileGrid,
+        movement_direction: MovementDirection,
+        turn_direction: TurnDirection,
+        entry: Duration,
+    ) -> HashSet<TileSlot> {
+        let path = turning_path(&Geometry::default(), movement_direction, turn_direction);
+        grid.claims(&path, SIZE, entry, SPEED)
+    }
+
+    fn tiles(claims: &HashSet<TileSlot>) -> HashSet<usize> {
+        claims.iter().map(|&(tile, _)| tile).collect()
+    }
+
+    #[test]
+    fn validate_rejects_degenerate_configs() {
+        let defaults = TileConfig::default();
+        assert!(defaults.validate().is_ok());
+        assert_eq!(
+            TileConfig { tiles_per_side: 0, ..defaults }.validate(),
+            Err("tiles_per_side must be at least 1".to_string())
+        );
+        assert_eq!(
+            TileConfig { slot: Duration::ZERO, ..defaults }.validate(),
+            Err("slot must be positive".to_string())
+        );
+        assert_eq!(
+            TileConfig { buffer: -1.0, ..defaults }.validate(),
+            Err("buffer must not be negative, got -1".to_string())
+        );
+    }
+
+    #[test]
+    fn straight_crossing_claims_a_strip_of_tiles() {
+        let grid = TileGrid::new(&Geometry::default(), TileConfig::default());
+        let claimed = tiles(&claims(&grid, MovementDirection::Down, TurnDirection::Straight, Duration::ZERO));
+
+        let n = grid.config().tiles_per_side;
+        let columns: HashSet<usize> = claimed.iter().map(|tile| tile % n).collect();
+        let rows: HashSet<usize> = claimed.iter().map(|tile| tile / n).collect();
+        assert_eq!(rows.len(), n, "the crossing spans the whole box");
+        assert!(columns.len() < n / 2, "the crossing stays in its lane, got columns {:?}", columns);
+    }
+
+    #[test]
+    fn claims_follow_the_entry_time() {
+        let grid = TileGrid::new(&Geometry::default(), TileConfig::default());
+        let early = claims(&grid, MovementDirection::Left, TurnDirection::Right, Duration::ZERO);
+        let same = claims(&grid, MovementDirection::Left, TurnDirection::Right, Duration::ZERO);
+        let late = claims(&grid, MovementDirection::Left, TurnDirection::Right, Duration::from_secs(5));
+
+        assert_eq!(early, same);
+        assert_eq!(tiles(&early), tiles(&late));
+        assert!(early.is_disjoint(&late));
+    }
+
+    #[test]
+    fn opposite_straight_crossings_share_no_tiles() {
+        let grid = TileGrid::new(&Geometry::default(), TileConfig::default());
+        let down = claims(&grid, MovementDirection::Down, TurnDirection::Straight, Duration::ZERO);
+        let up = claims(&grid, MovementDirection::Up, TurnDirection::Straight, Duration::ZERO);
+        let right = claims(&grid, MovementDirection::Right, TurnDirection::Straight, Duration::ZERO);
+
+        assert!(down.is_disjoint(&up));
+        assert!(!down.is_disjoint(&right));
+    }
+}
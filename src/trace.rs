@@ -0,0 +1,389 @@
+use crate::vehicle::{Lane, ReservationStatus, Vehicle};
+use crate::MovementDirection;
+use crate::TurnDirection;
+use std::io::Write;
+use std::time::Duration;
+
+// Per-vehicle quantity that can be written to a trace
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum TraceField {
+    Id,
+    Time,
+    // Written as two values, `x` and `y`
+    Position,
+    Heading,
+    Size,
+    Velocity,
+    Acceleration,
+    Lane,
+    MovementDirection,
+    TurnDirection,
+    ReservationStatus,
+    Link,
+}
+
+impl TraceField {
+    pub const ALL: [TraceField; 12] = [
+        TraceField::Id,
+        TraceField::Time,
+        TraceField::Position,
+        TraceField::Heading,
+        TraceField::Size,
+        TraceField::Velocity,
+        TraceField::Acceleration,
+        TraceField::Lane,
+        TraceField::MovementDirection,
+        TraceField::TurnDirection,
+        TraceField::ReservationStatus,
+        TraceField::Link,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            TraceField::Id => "id",
+            TraceField::Time => "time",
+            TraceField::Position => "position",
+            TraceField::Heading => "heading",
+            TraceField::Size => "size",
+            TraceField::Velocity => "velocity",
+            TraceField::Acceleration => "acceleration",
+            TraceField::Lane => "lane",
+            TraceField::MovementDirection => "movement_direction",
+            TraceField::TurnDirection => "turn_direction",
+            TraceField::ReservationStatus => "reservation_status",
+            TraceField::Link => "link",
+        }
+    }
+
+    pub fn parse(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|field| field.name() == name.to_ascii_lowercase())
+    }
+
+    // Parse a comma separated field list such as `id,time,position`
+    pub fn parse_list(list: &str) -> Result<Vec<Self>, String> {
+        let fields = list
+            .split(',')
+            .map(str::trim)
+            .map(|name| Self::parse(name).ok_or_else(|| format!("unknown trace field '{}'", name)))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(fields)
+    }
+
+    // Column names, one per written value
+    fn columns(&self) -> &'static [&'static str] {
+        match self {
+            TraceField::Position => &["x", "y"],
+            TraceField::Id => &["id"],
+            TraceField::Time => &["time"],
+            TraceField::Heading => &["heading"],
+            TraceField::Size => &["size"],
+            TraceField::Velocity => &["velocity"],
+            TraceField::Acceleration => &["acceleration"],
+            TraceField::Lane => &["lane"],
+            TraceField::MovementDirection => &["movement_direction"],
+            TraceField::TurnDirection => &["turn_direction"],
+            TraceField::ReservationStatus => &["reservation_status"],
+            TraceField::Link => &["link"],
+        }
+    }
+
+    fn values(&self, time: Duration, vehicle: &Vehicle) -> Vec<TraceValue> {
+        match self {
+            TraceField::Id => vec![TraceValue::Integer(vehicle.id as i64)],
+            TraceField::Time => vec![TraceValue::Number(time.as_secs_f64(), 4)],
+            TraceField::Position => vec![
+                TraceValue::Number(vehicle.position.x as f64, 2),
+                TraceValue::Number(vehicle.position.y as f64, 2),
+            ],
+            TraceField::Heading => vec![TraceValue::Number(vehicle.heading as f64, 4)],
+            TraceField::Size => vec![TraceValue::Number(vehicle.size as f64, 2)],
+            TraceField::Velocity => vec![TraceValue::Number(vehicle.velocity as f64, 2)],
+            TraceField::Acceleration => vec![TraceValue::Number(vehicle.acceleration as f64, 2)],
+            TraceField::Lane => vec![TraceValue::Text(lane_name(vehicle.lane))],
+            TraceField::MovementDirection => vec![TraceValue::Text(movement_direction_name(vehicle.movement_direction()))],
+            TraceField::TurnDirection => vec![TraceValue::Text(turn_direction_name(vehicle.turn_direction()))],
+            TraceField::ReservationStatus => vec![TraceValue::Text(reservation_status_name(vehicle.reservation_status))],
+            TraceField::Link => vec![TraceValue::Integer(vehicle.link as i64)],
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum TraceFormat {
+    Csv,
+    // One JSON object per vehicle and sample
+    JsonLines,
+}
+
+impl TraceFormat {
+    pub fn parse(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "csv" => Some(TraceFormat::Csv),
+            "jsonl" | "json-lines" => Some(TraceFormat::JsonLines),
+            _ => None,
+        }
+    }
+
+    // Format implied by a file name, CSV unless it ends in `.jsonl`
+    pub fn for_path(path: &str) -> Self {
+        if path.ends_with(".jsonl") {
+            TraceFormat::JsonLines
+        } else {
+            TraceFormat::Csv
+        }
+    }
+}
+
+// Receives the state of all vehicles whenever the simulation takes a trace sample
+pub trait TraceSink {
+    fn write_sample(&mut self, time: Duration, vehicles: &[Vehicle]) -> std::io::Result<()>;
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+// Writes the selected fields of every vehicle as CSV rows or JSON lines
+pub struct TraceWriter<W: Write> {
+    out: W,
+    format: TraceFormat,
+    fields: Vec<TraceField>,
+    header_written: bool,
+}
+
+impl<W: Write> TraceWriter<W> {
+    pub fn new(out: W, format: TraceFormat, fields: Vec<TraceField>) -> Self {
+        assert!(!fields.is_empty(), "a trace needs at least one field");
+        TraceWriter { out, format, fields, header_written: false }
+    }
+}
+
+impl TraceWriter<std::io::BufWriter<std::fs::File>> {
+    pub fn create(path: &str, format: TraceFormat, fields: Vec<TraceField>) -> std::io::Result<Self> {
+        let file = std::fs::File::create(path)?;
+        Ok(TraceWriter::new(std::io::BufWriter::new(file), format, fields))
+    }
+}
+
+impl<W: Write> TraceSink for TraceWriter<W> {
+    fn write_sample(&mut self, time: Duration, vehicles: &[Vehicle]) -> std::io::Result<()> {
+        if self.format == TraceFormat::Csv && !std::mem::replace(&mut self.header_written, true) {
+            let columns: Vec<&str> = self.fields.iter().flat_map(|field| field.columns().iter().copied()).collect();
+            writeln!(self.out, "{}", columns.join(","))?;
+        }
+
+        for vehicle in vehicles {
+            let mut entries = Vec::new();
+            for field in &self.fields {
+                entries.extend(field.columns().iter().copied().zip(field.values(time, vehicle)));
+            }
+            match self.format {
+                TraceFormat::Csv => {
+                    let values: Vec<String> = entries.iter().map(|(_, value)| value.to_string()).collect();
+                    writeln!(self.out, "{}", values.join(","))?;
+                }
+                TraceFormat::JsonLines => {
+                    let members: Vec<String> = entries
+                        .iter()
+                        .map(|(column, value)| match value {
+                            TraceValue::Text(text) => format!("\"{}\":\"{}\"", column, text),
+                            _ => format!("\"{}\":{}", column, value),
+                        })
+                        .collect();
+                    writeln!(self.out, "{{{}}}", members.join(","))?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.out.flush()
+    }
+}
+
+// A sink plus the simulation time of its next sample
+pub struct Tracer {
+    sink: Box<dyn TraceSink>,
+    // Simulation time between two samples, zero samples every tick
+    interval: Duration,
+    next_sample: Duration,
+    // First write error; the trace stops at that point
+    error: Option<std::io::Error>,
+}
+
+impl Tracer {
+    pub fn new(sink: Box<dyn TraceSink>, interval: Duration) -> Self {
+        Tracer { sink, interval, next_sample: Duration::ZERO, error: None }
+    }
+
+    // Called once per tick after the vehicles moved
+    pub fn sample(&mut self, time: Duration, vehicles: &[Vehicle]) {
+        if self.error.is_some() || time < self.next_sample {
+            return;
+        }
+        if let Err(e) = self.sink.write_sample(time, vehicles) {
+            self.error = Some(e);
+        }
+        if !self.interval.is_zero() {
+            while self.next_sample <= time {
+                self.next_sample += self.interval;
+            }
+        }
+    }
+
+    // Flush the sink and report the first error the trace ran into
+    pub fn finish(&mut self) -> std::io::Result<()> {
+        if let Some(e) = self.error.take() {
+            return Err(e);
+        }
+        self.sink.flush()
+    }
+}
+
+enum TraceValue {
+    Integer(i64),
+    // Value and number of decimals
+    Number(f64, usize),
+    Text(&'static str),
+}
+
+impl std::fmt::Display for TraceValue {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            TraceValue::Integer(value) => write!(f, "{}", value),
+            TraceValue::Number(value, decimals) => write!(f, "{:.*}", decimals, value),
+            TraceValue::Text(text) => write!(f, "{}", text),
+        }
+    }
+}
+
+fn lane_name(lane: Lane) -> &'static str {
+    match lane {
+        Lane::Left => "left",
+        Lane::Middle => "middle",
+        Lane::Right => "right",
+    }
+}
+
+fn movement_direction_name(movement_direction: MovementDirection) -> &'static str {
+    match movement_direction {
+        MovementDirection::Up => "up",
+        MovementDirection::Down => "down",
+        MovementDirection::Left => "left",
+        MovementDirection::Right => "right",
+    }
+}
+
+fn turn_direction_name(turn_direction: TurnDirection) -> &'static str {
+    match turn_direction {
+        TurnDirection::Left => "left",
+        TurnDirection::Straight => "straight",
+        TurnDirection::Right => "right",
+    }
+}
+
+fn reservation_status_name(reservation_status: ReservationStatus) -> &'static str {
+    match reservation_status {
+        ReservationStatus::NotRequested => "not_requested",
+        ReservationStatus::Granted => "granted",
+        ReservationStatus::Waiting => "waiting",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Position;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    fn vehicle(id: i32, x: f32, y: f32) -> Vehicle {
+        let mut vehicle =
+            Vehicle::new(MovementDirection::Down, TurnDirection::Left, 600.0, Position::new(x, y), Lane::Left);
+        vehicle.id = id;
+        vehicle.link = 3;
+        vehicle
+    }
+
+    fn write(format: TraceFormat, fields: Vec<TraceField>, samples: &[(f32, &[Vehicle])]) -> String {
+        let mut writer = TraceWriter::new(Vec::new(), format, fields);
+        for &(seconds, vehicles) in samples {
+            writer.write_sample(Duration::from_secs_f32(seconds), vehicles).unwrap();
+        }
+        String::from_utf8(writer.out).unwrap()
+    }
+
+    // Sink keeping the times it was sampled at
+    struct Times(Rc<RefCell<Vec<Duration>>>);
+
+    impl TraceSink for Times {
+        fn write_sample(&mut self, time: Duration, _vehicles: &[Vehicle]) -> std::io::Result<()> {
+            self.0.borrow_mut().push(time);
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn field_lists_are_parsed() {
+        assert_eq!(
+            TraceField::parse_list("id, Time,POSITION"),
+            Ok(vec![TraceField::Id, TraceField::Time, TraceField::Position])
+        );
+        assert_eq!(TraceField::parse_list("id,speed"), Err("unknown trace field 'speed'".to_string()));
+        for field in TraceField::ALL {
+            assert_eq!(TraceField::parse(field.name()), Some(field));
+        }
+    }
+
+    #[test]
+    fn formats_follow_the_name_or_extension() {
+        assert_eq!(TraceFormat::parse("JSONL"), Some(TraceFormat::JsonLines));
+        assert_eq!(TraceFormat::parse("xml"), None);
+        assert_eq!(TraceFormat::for_path("run.jsonl"), TraceFormat::JsonLines);
+        assert_eq!(TraceFormat::for_path("run.json"), TraceFormat::Csv);
+    }
+
+    #[test]
+    fn csv_has_one_header_and_a_row_per_vehicle() {
+        let vehicles = [vehicle(1, 10.0, 20.5), vehicle(2, -3.25, 0.0)];
+        let fields = vec![TraceField::Id, TraceField::Time, TraceField::Position, TraceField::Lane];
+        let text = write(TraceFormat::Csv, fields, &[(0.5, &vehicles), (1.0, &vehicles[..1])]);
+        assert_eq!(
+            text,
+            "id,time,x,y,lane\n\
+             1,0.5000,10.00,20.50,left\n\
+             2,0.5000,-3.25,0.00,left\n\
+             1,1.0000,10.00,20.50,left\n"
+        );
+    }
+
+    #[test]
+    fn json_lines_quote_text_only() {
+        let fields = vec![TraceField::Id, TraceField::Heading, TraceField::TurnDirection, TraceField::Link];
+        let text = write(TraceFormat::JsonLines, fields, &[(0.0, &[vehicle(7, 0.0, 0.0)])]);
+        assert_eq!(text, "{\"id\":7,\"heading\":1.5708,\"turn_direction\":\"left\",\"link\":3}\n");
+        let value: serde_json::Value = serde_json::from_str(text.trim()).unwrap();
+        assert_eq!(value["turn_direction"], "left");
+    }
+
+    #[test]
+    fn tracer_samples_at_its_interval() {
+        let times = Rc::new(RefCell::new(Vec::new()));
+        let mut tracer = Tracer::new(Box::new(Times(times.clone())), Duration::from_millis(250));
+        for tick in 0..=10 {
+            tracer.sample(Duration::from_millis(100 * tick), &[]);
+        }
+        let millis: Vec<u128> = times.borrow().iter().map(Duration::as_millis).collect();
+        assert_eq!(millis, [0, 300, 500, 800, 1000]);
+
+        // A zero interval samples every tick
+        let times = Rc::new(RefCell::new(Vec::new()));
+        let mut tracer = Tracer::new(Box::new(Times(times.clone())), Duration::ZERO);
+        for tick in 0..3 {
+            tracer.sample(Duration::from_millis(100 * tick), &[]);
+        }
+        assert_eq!(times.borrow().len(), 3);
+        assert!(tracer.finish().is_ok());
+    }
+}
@@ -1,7 +1,38 @@
+use crate::geometry::Geometry;
+use crate::path::{direction_vector, exit_direction, turning_path_from, Path};
+use crate::route::Route;
 use crate::MovementDirection;
 use crate::TurnDirection;
 use crate::Position;
-use std::ops::Sub;
+use std::time::Duration;
+
+// Side length of a vehicle built with `Vehicle::new`
+pub const DEFAULT_VEHICLE_SIZE: f32 = 55.0;
+// Initial velocity of spawned vehicles, in units per second
+pub const SPAWN_VELOCITY: f32 = 600.0;
+
+// Kind of vehicle the spawner can create; `weight` is its relative share of the traffic
+#[derive(Debug, Clone, PartialEq)]
+pub struct VehicleType {
+    pub name: String,
+    pub size: f32,
+    pub velocity: f32,
+    pub weight: f32,
+    // Reservation priority, see `PreemptionRule`; 0 for ordinary traffic
+    pub priority: u32,
+}
+
+impl Default for VehicleType {
+    fn default() -> Self {
+        VehicleType {
+            name: "car".to_string(),
+            size: DEFAULT_VEHICLE_SIZE,
+            velocity: SPAWN_VELOCITY,
+            weight: 1.0,
+            priority: 0,
+        }
+    }
+}
 
 #[derive(Debug, PartialEq, Clone, Copy)]
 pub enum Lane {
@@ -10,19 +41,67 @@ pub enum Lane {
     Right,
 }
 
+impl Lane {
+    // Lane a vehicle has to occupy to perform `turn_direction`
+    pub fn for_turn(turn_direction: TurnDirection) -> Self {
+        match turn_direction {
+            TurnDirection::Left => Lane::Left,
+            TurnDirection::Straight => Lane::Middle,
+            TurnDirection::Right => Lane::Right,
+        }
+    }
+}
+
+// State of a vehicle's request for a slot in the intersection
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum ReservationStatus {
+    NotRequested,
+    Granted,
+    // Denied, the vehicle holds before the stop line and asks again every tick
+    Waiting,
+}
+
+// Progress of a vehicle along its path through the intersection
+#[derive(Debug, Clone)]
+pub struct TurnProgress {
+    pub path: Path,
+    // Arc length already travelled along `path`
+    pub distance: f32,
+    pub exit_direction: MovementDirection,
+}
 
 #[derive(Debug)]
 pub struct Vehicle {
     pub id: i32,
     pub size: f32,
-    pub  movement_direction: MovementDirection,
-    pub turn_direction: TurnDirection,
+    // Turns at every intersection on the way; the current one is `turn_direction()`
+    pub route: Route,
     pub velocity: f32,
+    // Cruising speed the vehicle returns to whenever nothing forces it to slow down
+    pub desired_velocity: f32,
+    // Distance from the front bumper to the stop line, 0 once the front has reached it
     pub distance_to_intersection: f32,
     pub time_to_intersection: f32,
     pub position: Position,
     pub acceleration: f32,
     pub lane: Lane,
+    // Road network link the vehicle is travelling on
+    pub link: usize,
+    pub spawn_time: Duration,
+    // Node whose box the front entered and when, until the rear is out of it
+    pub box_entry: Option<(usize, Duration)>,
+    // Distance covered along the road since spawning
+    pub distance_travelled: f32,
+    // Orientation in radians, 0 pointing along +x and growing clockwise on screen.
+    // Together with `velocity` this is the authoritative motion state; the
+    // cardinal `movement_direction()` is derived from it.
+    pub heading: f32,
+    // Set while the vehicle is following its turning path
+    pub turn_progress: Option<TurnProgress>,
+    pub crossed_intersection: bool,
+    pub reservation_status: ReservationStatus,
+    // Reservation priority taken from the vehicle type
+    pub priority: u32,
 }
 
 impl Vehicle {
@@ -35,21 +114,82 @@ impl Vehicle {
     ) -> Self {
         Vehicle {
             id: 0,
-            size: 55.0, // Let's say every vehicle has a size of 10 units for now
-            movement_direction,
-            turn_direction,
+            size: DEFAULT_VEHICLE_SIZE,
+            route: Route::single(turn_direction),
             velocity,
+            desired_velocity: velocity,
             distance_to_intersection: 0.0,
             time_to_intersection: 0.0,
             position,
             acceleration: 0.0, // Default value
             lane,
+            link: 0,
+            spawn_time: Duration::ZERO,
+            box_entry: None,
+            distance_travelled: 0.0,
+            heading: heading_of(movement_direction),
+            turn_progress: None,
+            crossed_intersection: false,
+            reservation_status: ReservationStatus::NotRequested,
+            priority: 0,
+        }
+    }
+
+    // Turn at the intersection the vehicle is approaching or crossing
+    pub fn turn_direction(&self) -> TurnDirection {
+        self.route.current()
+    }
+
+    // Cardinal direction closest to the current heading
+    pub fn movement_direction(&self) -> MovementDirection {
+        let (dx, dy) = self.direction();
+        if dx.abs() >= dy.abs() {
+            if dx >= 0.0 { MovementDirection::Right } else { MovementDirection::Left }
+        } else if dy >= 0.0 {
+            MovementDirection::Down
+        } else {
+            MovementDirection::Up
         }
     }
 
-    pub fn update_distance_and_time_to_intersection(&mut self) {
-        // Assuming intersection is at (0,0)
-        self.distance_to_intersection = self.position.sub(Position::new(0.0, 0.0));
+    // Unit vector pointing along the heading
+    pub fn direction(&self) -> (f32, f32) {
+        (self.heading.cos(), self.heading.sin())
+    }
+
+    pub fn velocity_vector(&self) -> (f32, f32) {
+        let (dx, dy) = self.direction();
+        (dx * self.velocity, dy * self.velocity)
+    }
+
+    // Coordinate along the direction of travel, growing as the vehicle moves forward
+    pub fn longitudinal_position(&self) -> f32 {
+        self.longitudinal_coordinate(self.position)
+    }
+
+    // Coordinate across the direction of travel, identifying the lane actually occupied
+    pub fn lateral_position(&self) -> f32 {
+        let (dx, dy) = self.direction();
+        self.position.x * dy - self.position.y * dx
+    }
+
+    // Projection of an arbitrary point onto this vehicle's direction of travel
+    pub fn longitudinal_coordinate(&self, point: Position) -> f32 {
+        let (dx, dy) = self.direction();
+        point.x * dx + point.y * dy
+    }
+
+    // Corners of the vehicle footprint, a square of side `size` rotated by `heading`
+    pub fn corners(&self) -> [Position; 4] {
+        footprint(self.position, self.heading, self.size)
+    }
+
+    pub fn update_distance_and_time_to_intersection(&mut self, geometry: &Geometry) {
+        self.distance_to_intersection = if self.crossed_intersection || self.turn_progress.is_some() {
+            0.0
+        } else {
+            (-self.distance_past_intersection_entry(geometry) - self.size / 2.0).max(0.0)
+        };
         if self.velocity != 0.0 {
             self.time_to_intersection = self.distance_to_intersection / self.velocity;
         } else {
@@ -57,34 +197,108 @@ impl Vehicle {
         }
     }
 
-    pub fn update_direction_at_intersection(&mut self) {
-        // Set turn direction based on the lane
-        match self.lane {
-            Lane::Left => self.turn_direction = TurnDirection::Left,
-            Lane::Middle => self.turn_direction = TurnDirection::Straight,
-            Lane::Right => self.turn_direction = TurnDirection::Right,
+    // Distance the center has travelled past the edge of the intersection box it
+    // enters through, negative while still approaching
+    pub fn distance_past_intersection_entry(&self, geometry: &Geometry) -> f32 {
+        let (entry_edge, _) = geometry.intersection_extent(self.movement_direction());
+        self.longitudinal_position() - entry_edge
+    }
+
+    // True when the vehicle should start its turning path on this tick
+    pub fn at_intersection_entry(&self, geometry: &Geometry) -> bool {
+        !self.crossed_intersection && self.turn_progress.is_none() && self.distance_past_intersection_entry(geometry) >= 0.0
+    }
+
+    // Checks that the lane the vehicle actually occupies allows the turn its route takes here
+    pub fn check_lane(&self, geometry: &Geometry) -> Result<(), String> {
+        let turn_direction = self.turn_direction();
+        match geometry.lane_at(self.movement_direction(), self.position) {
+            Some(lane) if geometry.lane_allows(lane, turn_direction) => Ok(()),
+            Some(lane) => Err(format!(
+                "vehicle {} in lane {} cannot turn {:?}, that turn needs lane {}",
+                self.id,
+                lane,
+                turn_direction,
+                geometry.lane_index(turn_direction)
+            )),
+            None => Err(format!("vehicle {} is not on a lane of its approach", self.id)),
         }
+    }
+
+    // Path through the box of `geometry` the vehicle takes from its current lane. It
+    // leaves on the lane needed for the next turn of the route, or on the lane
+    // matching this turn when the route ends here.
+    pub fn planned_path(&self, geometry: &Geometry) -> Path {
+        let turn_direction = self.turn_direction();
+        let exit_lane = geometry.lane_index(self.route.next().unwrap_or(turn_direction));
+
+        // Where the vehicle crosses, or crossed, into the box along its current line
+        let movement_direction = self.movement_direction();
+        let past_entry = self.distance_past_intersection_entry(geometry);
+        let (dx, dy) = direction_vector(movement_direction);
+        let entry = Position::new(self.position.x - dx * past_entry, self.position.y - dy * past_entry);
+        turning_path_from(geometry, entry, movement_direction, turn_direction, exit_lane)
+    }
+
+    pub fn update_direction_at_intersection(&mut self, geometry: &Geometry) {
+        // Start the turning path where the vehicle crossed into the box, keeping
+        // the distance it already travelled past the edge on this tick
+        let movement_direction = self.movement_direction();
+        let turn_direction = self.turn_direction();
+        let overshoot = self.distance_past_intersection_entry(geometry).max(0.0);
+        let path = self.planned_path(geometry);
+        let (position, heading) = path.sample(overshoot);
+        self.position = position;
+        self.heading = heading;
+        self.turn_progress = Some(TurnProgress {
+            path,
+            distance: overshoot,
+            exit_direction: exit_direction(movement_direction, turn_direction),
+        });
+    }
+
+    // Move `distance` further along the turning path. Once its end is reached the
+    // vehicle takes the exit direction and keeps the remaining distance on the exit lane.
+    pub fn advance_along_turn(&mut self, distance: f32) {
+        let progress = match self.turn_progress.as_mut() {
+            Some(progress) => progress,
+            None => return,
+        };
+        progress.distance += distance;
 
-        // Now, update the movement direction based on the turn direction
-        match self.turn_direction {
-            TurnDirection::Straight => {}, // No change in movement direction
-            TurnDirection::Left => {
-                self.movement_direction = match self.movement_direction {
-                    MovementDirection::Up => MovementDirection::Left,
-                    MovementDirection::Down => MovementDirection::Right,
-                    MovementDirection::Left => MovementDirection::Down,
-                    MovementDirection::Right => MovementDirection::Up,
-                };
-            },
-            TurnDirection::Right => {
-                self.movement_direction = match self.movement_direction {
-                    MovementDirection::Up => MovementDirection::Right,
-                    MovementDirection::Down => MovementDirection::Left,
-                    MovementDirection::Left => MovementDirection::Up,
-                    MovementDirection::Right => MovementDirection::Down,
-                };
-            }
+        if progress.distance < progress.path.length() {
+            let (position, heading) = progress.path.sample(progress.distance);
+            self.position = position;
+            self.heading = heading;
+            return;
         }
+
+        let remaining = progress.distance - progress.path.length();
+        let end = progress.path.end();
+        let (dx, dy) = direction_vector(progress.exit_direction);
+        self.position = Position::new(end.x + dx * remaining, end.y + dy * remaining);
+        self.heading = heading_of(progress.exit_direction);
+        self.turn_progress = None;
+        self.crossed_intersection = true;
     }
 }
 
+// Corners of a square of side `size` centered on `position` and rotated by `heading`
+pub fn footprint(position: Position, heading: f32, size: f32) -> [Position; 4] {
+    let (dx, dy) = (heading.cos(), heading.sin());
+    let half = size / 2.0;
+    let (fx, fy) = (dx * half, dy * half); // forward half extent
+    let (sx, sy) = (-dy * half, dx * half); // sideways half extent
+    let (x, y) = (position.x, position.y);
+    [
+        Position::new(x + fx + sx, y + fy + sy),
+        Position::new(x + fx - sx, y + fy - sy),
+        Position::new(x - fx - sx, y - fy - sy),
+        Position::new(x - fx + sx, y - fy + sy),
+    ]
+}
+
+pub fn heading_of(movement_direction: MovementDirection) -> f32 {
+    let (dx, dy) = direction_vector(movement_direction);
+    dy.atan2(dx)
+}